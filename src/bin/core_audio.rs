@@ -62,9 +62,6 @@ impl AudioBlock {
         }
     }
 
-    fn centre_samples(&self) -> [i16; AUDIO_BUFFER_SIZE] {
-        self.samples.map(|x| (x as i16) - 2048)
-    }
 }
 
 #[cortex_m_rt::entry]
@@ -186,10 +183,6 @@ async fn cdc_tx_task(cdc: &'static mut CdcAcmClass<'static, Driver<'static, USB>
         // Drain audio blocks while connected
         loop {
             let block: AudioBlock = AUDIO_CHANNEL.receive().await;
-            block.centre_samples();
-            block.centre_samples();
-            block.centre_samples();
-            block.centre_samples();
             let bytes: &[u8] = bytemuck::cast_slice(&block.samples);
 
             if let Err(e) = write_cdc_chunked(cdc, bytes).await {
@@ -0,0 +1,155 @@
+//! Lets a freshly-flashed board (with no WiFi credentials baked into
+//! `.env`) be configured over-the-air instead of requiring a recompile:
+//! boots as its own access point, serves a `/provision` route that accepts
+//! an SSID + password, then leaves the access point and joins that network
+//! as a station.
+
+#![no_std]
+#![no_main]
+#![allow(async_fn_in_trait)]
+#![feature(type_alias_impl_trait)]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_sync::mutex::Mutex;
+use embassy_time::Duration;
+use heapless::String;
+use picoserve::extract::Json;
+use picoserve::extract::State;
+use picoserve::{
+    AppRouter, AppWithStateBuilder,
+    routing::{PathRouter, get, post},
+};
+use pinot_voir::common::shared_functions::blink_n_times;
+use pinot_voir::common::wifi::{EmbassyPicoWifiCore, SharedEmbassyWifiPicoCore, WEB_TASK_POOL_SIZE};
+use serde::Deserialize;
+use static_cell::make_static;
+
+use {defmt_rtt as _, panic_probe as _};
+
+const PROVISIONING_SSID: &str = "pinot-voir-setup";
+
+#[derive(Deserialize)]
+struct ProvisionRequest {
+    ssid: String<32>,
+    password: String<64>,
+}
+
+impl picoserve::extract::FromRef<SharedEmbassyWifiPicoCore> for SharedEmbassyWifiPicoCore {
+    fn from_ref(state: &SharedEmbassyWifiPicoCore) -> Self {
+        *state
+    }
+}
+
+struct AppProps;
+
+impl AppWithStateBuilder for AppProps {
+    type State = SharedEmbassyWifiPicoCore;
+    type PathRouter = impl PathRouter<SharedEmbassyWifiPicoCore>;
+
+    fn build_app(self) -> picoserve::Router<Self::PathRouter, Self::State> {
+        picoserve::Router::new()
+            .route(
+                "/",
+                get(|| async move { "Send WiFi credentials to POST /provision." }),
+            )
+            .route(
+                "/provision",
+                post(
+                    |State(SharedEmbassyWifiPicoCore(wifi_core)): State<SharedEmbassyWifiPicoCore>,
+                     Json(request): Json<ProvisionRequest>| async move {
+                        info!(
+                            "Provisioning request received for SSID: {}",
+                            request.ssid.as_str()
+                        );
+                        let mut wifi_core = wifi_core.lock().await;
+                        match wifi_core
+                            .reconfigure_as_station(request.ssid.as_str(), request.password.as_str())
+                            .await
+                        {
+                            Ok(_) => "Joined network, leaving provisioning mode.",
+                            Err(_) => "Failed to join network; still in provisioning mode.",
+                        }
+                    },
+                ),
+            )
+    }
+}
+
+#[embassy_executor::task(pool_size = WEB_TASK_POOL_SIZE)]
+async fn web_task(
+    id: usize,
+    stack: embassy_net::Stack<'static>,
+    app: &'static AppRouter<AppProps>,
+    config: &'static picoserve::Config<Duration>,
+    state: SharedEmbassyWifiPicoCore,
+) -> ! {
+    let port = 80;
+    let mut tcp_rx_buffer = [0; 1024];
+    let mut tcp_tx_buffer = [0; 1024];
+    let mut http_buffer = [0; 2048];
+
+    picoserve::listen_and_serve_with_state(
+        id,
+        app,
+        config,
+        stack,
+        port,
+        &mut tcp_rx_buffer,
+        &mut tcp_tx_buffer,
+        &mut http_buffer,
+        &state,
+    )
+    .await
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+    info!("Hello World!");
+
+    let mut embassy_pico_wifi_core = EmbassyPicoWifiCore::start_ap(
+        p.PIN_23,
+        p.PIN_24,
+        p.PIN_25,
+        p.PIN_29,
+        p.PIO0,
+        p.DMA_CH0,
+        spawner,
+        PROVISIONING_SSID,
+        None,
+    )
+    .await;
+
+    blink_n_times(&mut embassy_pico_wifi_core.control, 1).await;
+
+    let app = make_static!(AppProps.build_app());
+
+    info!("Starting provisioning web server");
+
+    let config = make_static!(
+        picoserve::Config::new(picoserve::Timeouts {
+            start_read_request: Some(Duration::from_secs(5)),
+            persistent_start_read_request: Some(Duration::from_secs(1)),
+            read_request: Some(Duration::from_secs(1)),
+            write: Some(Duration::from_secs(1)),
+        })
+        .keep_connection_alive()
+    );
+
+    let shared_wifi_core =
+        SharedEmbassyWifiPicoCore(make_static!(Mutex::new(embassy_pico_wifi_core)));
+
+    for id in 0..WEB_TASK_POOL_SIZE {
+        spawner.must_spawn(web_task(
+            id,
+            shared_wifi_core.0.lock().await.stack,
+            app,
+            config,
+            shared_wifi_core,
+        ));
+    }
+
+    info!("Provisioning server started on 192.168.4.1");
+}
@@ -17,10 +17,15 @@ use embassy_net::dns::DnsSocket;
 use embassy_net::tcp::client::TcpConnection;
 use embassy_net::tcp::client::{TcpClient, TcpClientState};
 use embassy_rp::clocks::RoscRng;
+use embassy_rp::flash::{Blocking, Flash};
+use embassy_rp::peripherals::FLASH;
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
-use embassy_time::{Delay, Duration, Timer};
+use embassy_time::{Delay, Duration, Instant, Timer};
 use picoserve::extract::State;
+#[cfg(feature = "bluetooth")]
+use pinot_voir::common::ble::ble_dht22_task;
 use pinot_voir::common::dht22_tools::{DHT22, DHT22ReadingResponse};
+use pinot_voir::common::flash_buffer::{BufferedReading, FlashRingBuffer};
 use pinot_voir::common::shared_functions::{EnvironmentVariables, blink_n_times};
 use pinot_voir::common::supabase::{construct_post_request_arguments, read_http_response};
 use pinot_voir::common::wifi::{EmbassyPicoWifiCore, HttpBuffers, WEB_TASK_POOL_SIZE};
@@ -37,6 +42,13 @@ use static_cell::make_static;
 
 use {defmt_rtt as _, panic_probe as _};
 
+/// Total size of the RP2040 Pico W's on-board QSPI flash.
+const FLASH_SIZE: usize = 2 * 1024 * 1024;
+/// Reserve the last 4 sectors (16 KB) of flash for the offline readings
+/// buffer, well clear of the program image.
+const OFFLINE_BUFFER_BASE: u32 = FLASH_SIZE as u32 - 4 * 4096;
+const OFFLINE_BUFFER_LEN: u32 = 4 * 4096;
+
 struct AppProps;
 
 impl AppWithStateBuilder for AppProps {
@@ -181,10 +193,20 @@ async fn main(spawner: Spawner) {
 
     info!("Web server started");
 
+    #[cfg(feature = "bluetooth")]
+    unwrap!(spawner.spawn(ble_dht22_task(
+        embassy_pico_wifi_core
+            .bluetooth
+            .take()
+            .expect("bluetooth already taken"),
+        shared_sensor.0,
+    )));
+
     unwrap!(spawner.spawn(read_sensor(
         shared_sensor,
         environment_variables,
         embassy_pico_wifi_core.stack,
+        p.FLASH,
     )));
 }
 
@@ -193,7 +215,12 @@ async fn read_sensor(
     sensor: SharedSensor<Delay>,
     environment_variables: &'static EnvironmentVariables,
     stack: Stack<'static>,
+    flash_peripheral: FLASH,
 ) {
+    let flash = Flash::<_, Blocking, FLASH_SIZE>::new_blocking(flash_peripheral);
+    let mut offline_buffer =
+        FlashRingBuffer::<_, OFFLINE_BUFFER_BASE, OFFLINE_BUFFER_LEN>::new(flash)
+            .expect("failed to recover offline readings buffer");
     let mut rng = RoscRng;
     let seed = rng.next_u64();
     let mut http_buffers: HttpBuffers = HttpBuffers::new();
@@ -215,37 +242,78 @@ async fn read_sensor(
         hum: 0.0,
     };
     loop {
+        // Replay anything buffered from a past outage, oldest first, before
+        // posting the latest reading.
+        while let Ok(Some(buffered)) = offline_buffer.peek_oldest() {
+            let buffered_reading = Reading {
+                temp: buffered.temperature,
+                hum: buffered.humidity,
+            };
+            if post_reading(&mut http_client, &mut http_buffers, environment_variables, buffered_reading)
+                .await
+                .is_err()
+            {
+                break;
+            }
+            let _ = offline_buffer.pop_oldest();
+        }
+
         let dht_reading = sensor.0.lock().await.read().unwrap_or(blank_reading);
         info!(
             "Temp = {}, Humi = {}",
             dht_reading.get_temp(),
             dht_reading.get_hum()
         );
-        let (dht_reading_as_string, headers) =
-            construct_post_request_arguments(dht_reading, environment_variables)
-                .expect("Failed to read dht reading");
-        let mut request = match http_client
-            .request(Method::POST, environment_variables.supabase_url)
+
+        if post_reading(&mut http_client, &mut http_buffers, environment_variables, dht_reading)
             .await
+            .is_err()
         {
-            Ok(req) => req,
-            Err(e) => {
-                error!("Failed to make HTTP request: {:?}", e);
-                return; // handle the error
+            let buffered = BufferedReading {
+                timestamp_secs: Instant::now().as_secs(),
+                temperature: dht_reading.get_temp(),
+                humidity: dht_reading.get_hum(),
+            };
+            if let Err(e) = offline_buffer.push(buffered) {
+                error!("Offline readings buffer full, dropping reading: {:?}", e);
             }
         }
-        .headers(&headers)
-        .body(dht_reading_as_string.as_bytes());
-        let response: Response<'_, '_, HttpConnection<'_, TcpConnection<'_, 1, 1024, 1024>>> =
-            match request.send(&mut http_buffers.rx_buffer).await {
-                Ok(resp) => resp,
-                Err(_e) => {
-                    error!("Failed to send HTTP request");
-                    return; // handle the error;
-                }
-            };
 
-        read_http_response(response).await;
         Timer::after(delay_loop).await;
     }
 }
+
+/// POSTs a single DHT22 reading to Supabase, returning `Err(())` on any
+/// HTTP/TLS failure so the caller can buffer it instead of losing it.
+async fn post_reading<'a>(
+    http_client: &mut HttpClient<'a, &TcpClient<'a, 1, 1024, 1024>, &DnsSocket<'a>>,
+    http_buffers: &mut HttpBuffers,
+    environment_variables: &EnvironmentVariables,
+    dht_reading: Reading<f32, f32>,
+) -> Result<(), ()> {
+    let (dht_reading_as_string, headers) =
+        construct_post_request_arguments(dht_reading, environment_variables).map_err(|_| ())?;
+    let mut request = match http_client
+        .request(Method::POST, environment_variables.supabase_url)
+        .await
+    {
+        Ok(req) => req,
+        Err(e) => {
+            error!("Failed to make HTTP request: {:?}", e);
+            return Err(());
+        }
+    }
+    .headers(&headers)
+    .body(dht_reading_as_string.as_bytes());
+    let response: Response<'_, '_, HttpConnection<'_, TcpConnection<'_, 1, 1024, 1024>>> =
+        match request.send(&mut http_buffers.rx_buffer).await {
+            Ok(resp) => resp,
+            Err(_e) => {
+                error!("Failed to send HTTP request");
+                return Err(());
+            }
+        };
+
+    read_http_response(response).await;
+    Ok(())
+}
@@ -1,6 +1,10 @@
 //! Create a server using picoserver on a Raspberry Pi Pico W.
 //! Read the DHT22 sensor and expose the temperature and humidity readings via the server.
-//! Additionally, send the readings to a Supabase database on a loop.
+//! Additionally, publish sensor readings to an MQTT broker on a fixed interval, and accept
+//! `set_led`/`disconnect` commands back over MQTT alongside the HTTP routes above.
+//! Also samples the ADC directly (no Core1 split, unlike `bin/core_audio.rs`'s USB path) and
+//! feeds it through `common::audio_analysis::run_loudness_analysis` so `/read_loudness` has a
+//! real RMS figure to serve instead of always returning `null`.
 
 #![no_std]
 #![no_main]
@@ -9,17 +13,37 @@
 #![feature(impl_trait_in_assoc_type)]
 use defmt::*;
 use embassy_executor::Spawner;
+use embassy_rp::Peri;
+use embassy_rp::adc::{Adc, Channel as AdcChannel, Config as AdcConfig, InterruptHandler as ADCInterruptHandler};
+use embassy_rp::bind_interrupts;
+use embassy_rp::gpio::Pull;
+use embassy_rp::peripherals::{ADC, DMA_CH1, PIN_26};
+use embassy_sync::channel::Channel as SyncChannel;
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
-use embassy_time::{Delay, Duration};
+use embassy_time::{Delay, Duration, Timer};
 use picoserve::extract::Json;
 use picoserve::extract::State;
+use pinot_voir::common::audio_analysis::run_loudness_analysis;
 use pinot_voir::common::dht22_tools::DHT22;
+use pinot_voir::common::mqtt::mqtt_telemetry_task;
+#[cfg(feature = "perf_test")]
+use pinot_voir::common::perf::run_throughput_self_test;
 use pinot_voir::common::sensor_tools::SensorState;
 use pinot_voir::common::shared_functions::{EnvironmentVariables, blink_n_times};
 use pinot_voir::common::wifi::{
     EmbassyPicoWifiCore, SharedEmbassyWifiPicoCore, WEB_TASK_POOL_SIZE, wifi_autoheal_task,
 };
 
+bind_interrupts!(struct IrqsADC {
+    ADC_IRQ_FIFO => ADCInterruptHandler;
+});
+
+/// Sample count of each block handed to the loudness analysis channel,
+/// matching `bin/core_audio.rs`'s `AudioBlock`.
+const AUDIO_BUFFER_SIZE: usize = 512;
+static LOUDNESS_CHANNEL: SyncChannel<CriticalSectionRawMutex, [i16; AUDIO_BUFFER_SIZE], 4> =
+    SyncChannel::new();
+
 use picoserve::{
     AppRouter, AppWithStateBuilder,
     response::DebugValue,
@@ -30,6 +54,10 @@ use static_cell::make_static;
 
 use {defmt_rtt as _, panic_probe as _};
 
+/// MQTT client-id this board identifies itself with, and the prefix its
+/// publish/subscribe topics (`pinot/<MQTT_CLIENT_ID>/...`) are built from.
+const MQTT_CLIENT_ID: &str = "pinot-voir-server";
+
 struct AppProps;
 
 impl AppWithStateBuilder for AppProps {
@@ -86,10 +114,73 @@ impl AppWithStateBuilder for AppProps {
                     Json(*sensor_state)
                 }),
             )
+            .route(
+                "/read_loudness",
+                get(|State(app_state): State<AppState>| async move {
+                    let sensor_state = app_state.shared_sensor_state.0.lock().await;
+                    Json(sensor_state.loudness)
+                }),
+            )
         // ...existing code...
     }
 }
 
+/// Samples the ADC directly on a single core (server.rs has no multicore
+/// split, unlike `bin/core_audio.rs`), centres each block around zero and
+/// hands it to [`loudness_analysis_task`] over [`LOUDNESS_CHANNEL`].
+#[embassy_executor::task]
+async fn adc_task(adc_peripheral: Peri<'static, ADC>, dma: Peri<'static, DMA_CH1>, pin: Peri<'static, PIN_26>) {
+    info!("ADC task starting");
+
+    let mut adc = Adc::new(adc_peripheral, IrqsADC, AdcConfig::default());
+    let mut adc_pin = AdcChannel::new_pin(pin, Pull::None);
+
+    const SAMPLE_RATE_HZ: u32 = 44100;
+    const ADC_DIV: u16 = (48_000_000 / SAMPLE_RATE_HZ - 1) as u16;
+
+    let mut dma = dma;
+    let mut samples = [0u16; AUDIO_BUFFER_SIZE];
+
+    loop {
+        match adc
+            .read_many(&mut adc_pin, &mut samples, ADC_DIV, dma.reborrow())
+            .await
+        {
+            Ok(_) => {
+                let centred_samples = samples.map(|x| (x as i16) - 2048);
+                LOUDNESS_CHANNEL.send(centred_samples).await;
+            }
+            Err(_) => {
+                error!("ADC read error");
+                Timer::after_millis(1).await;
+            }
+        }
+    }
+}
+
+/// Drains [`LOUDNESS_CHANNEL`] and writes the computed RMS loudness into the
+/// same `SensorState` `/read_loudness` and `/read_sensor` serve.
+#[embassy_executor::task]
+async fn loudness_analysis_task(
+    sensor_state: &'static Mutex<CriticalSectionRawMutex, SensorState>,
+) -> ! {
+    run_loudness_analysis(LOUDNESS_CHANNEL.receiver(), sensor_state).await
+}
+
+/// Runs the Wi-Fi/TCP throughput self-test once at boot and reports the
+/// results over `defmt::info!`, so antenna placement and power-save
+/// regressions show up in a HIL run without touching the sensor loop.
+/// Only built with the `perf_test` feature enabled.
+#[cfg(feature = "perf_test")]
+#[embassy_executor::task]
+async fn perf_self_test_task(
+    shared_wifi_core: SharedEmbassyWifiPicoCore,
+    environment_variables: &'static EnvironmentVariables,
+) {
+    let stack = shared_wifi_core.0.lock().await.stack;
+    run_throughput_self_test(stack, environment_variables, Duration::from_secs(10)).await;
+}
+
 #[embassy_executor::task(pool_size = WEB_TASK_POOL_SIZE)]
 async fn web_task(
     id: usize,
@@ -203,6 +294,28 @@ async fn main(spawner: Spawner) {
         .spawn(wifi_autoheal_task(shared_wifi_core, environment_variables))
         .unwrap();
 
+    spawner
+        .spawn(mqtt_telemetry_task(
+            shared_wifi_core,
+            shared_sensor_state.0,
+            environment_variables,
+            MQTT_CLIENT_ID,
+        ))
+        .unwrap();
+
+    spawner
+        .spawn(adc_task(p.ADC, p.DMA_CH1, p.PIN_26))
+        .unwrap();
+
+    spawner
+        .spawn(loudness_analysis_task(shared_sensor_state.0))
+        .unwrap();
+
+    #[cfg(feature = "perf_test")]
+    spawner
+        .spawn(perf_self_test_task(shared_wifi_core, environment_variables))
+        .unwrap();
+
     // for some reason, idk why, I can only spawn one less than the pool size
     // otherwise it panics
     for id in 1..(WEB_TASK_POOL_SIZE - 3) {
@@ -1,6 +1,9 @@
 //! Create a server using picoserver on a Raspberry Pi Pico W.
 //! Read the DHT22 sensor and expose the temperature and humidity readings via the server.
-//! Additionally, send the readings to a Supabase database on a loop.
+//! Additionally, broadcast ADC samples over UDP: with the `proto-ipv6` feature enabled, the
+//! stack also brings up SLAAC IPv6 and the broadcast moves to an `ff02::` link-local multicast
+//! group instead of a v4 limited broadcast, since many networks forward the former but not the
+//! latter.
 
 #![no_std]
 #![no_main]
@@ -14,6 +17,8 @@ use cyw43::Control;
 use defmt::info;
 use embassy_executor::Spawner;
 use embassy_net::udp::{PacketMetadata, UdpMetadata, UdpSocket};
+#[cfg(feature = "proto-ipv6")]
+use embassy_net::Ipv6Address;
 use embassy_net::{IpAddress, IpEndpoint};
 use embassy_rp::Peri;
 use embassy_rp::adc::{Adc, Channel, Config, InterruptHandler};
@@ -31,17 +36,51 @@ use picoserve::{
     response::DebugValue,
     routing::{PathRouter, get, parse_path_segment},
 };
+#[cfg(feature = "bluetooth")]
+use pinot_voir::common::ble::{SharedSensorState as BleSharedSensorState, ble_task};
 use pinot_voir::common::dht22_tools::DHT22;
 use pinot_voir::common::sensor_tools::SensorState;
 use pinot_voir::common::shared_functions::{EnvironmentVariables, blink_n_times};
 use pinot_voir::common::wifi::{
-    EmbassyPicoWifiCore, SharedEmbassyWifiPicoCore, WEB_TASK_POOL_SIZE, wifi_autoheal_task,
+    EmbassyPicoWifiCore, NetworkConfig, SharedEmbassyWifiPicoCore, WEB_TASK_POOL_SIZE,
+    wifi_autoheal_task,
 };
 
 use static_cell::make_static;
 
 use {defmt_rtt as _, panic_probe as _};
 
+/// Path-segment values accepted by the `/power_mode/<mode>` route, mapping
+/// onto [`cyw43::PowerManagementMode`]'s power-save and full-performance
+/// variants (the two ends of the tradeoff callers actually want to pick
+/// between at runtime).
+#[derive(Clone, Copy)]
+enum PowerModeParam {
+    PowerSave,
+    Performance,
+}
+
+impl core::str::FromStr for PowerModeParam {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "power_save" => Ok(PowerModeParam::PowerSave),
+            "performance" => Ok(PowerModeParam::Performance),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<PowerModeParam> for cyw43::PowerManagementMode {
+    fn from(mode: PowerModeParam) -> Self {
+        match mode {
+            PowerModeParam::PowerSave => cyw43::PowerManagementMode::PowerSave,
+            PowerModeParam::Performance => cyw43::PowerManagementMode::Performance,
+        }
+    }
+}
+
 struct AppProps;
 
 impl AppWithStateBuilder for AppProps {
@@ -85,6 +124,18 @@ impl AppWithStateBuilder for AppProps {
                     Json(*sensor_state)
                 }),
             )
+            .route(
+                ("/power_mode", parse_path_segment()),
+                get(
+                    |mode: PowerModeParam,
+                     State(SharedEmbassyWifiPicoCore(wifi_core)): State<
+                        SharedEmbassyWifiPicoCore,
+                    >| async move {
+                        wifi_core.lock().await.set_power_management(mode.into()).await;
+                        "OK"
+                    },
+                ),
+            )
         // ...existing code...
     }
 }
@@ -154,16 +205,40 @@ async fn udp_stream(
     // }
 
     let sample_frequency_s: u64 = 44100000;
-    let broadcast_addr = IpEndpoint::new(IpAddress::v4(255, 255, 255, 255), port);
-    let mut socket = UdpSocket::new(
-        shared_wifi_core.0.lock().await.stack,
-        &mut rx_meta,
-        &mut rx_buffer,
-        &mut tx_meta,
-        &mut tx_buffer,
-    );
+    let stack = shared_wifi_core.0.lock().await.stack;
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buffer, &mut tx_meta, &mut tx_buffer);
     socket.bind(port).expect("Could not bind UDP sensor.");
 
+    // Prefer the link-local IPv6 multicast group this board acquired SLAAC
+    // config for, if any: plenty of networks drop v4 limited broadcasts at
+    // the router but forward `ff02::` multicast within the local link.
+    // Falls back to the v4 broadcast address when IPv6 wasn't configured
+    // (either the `proto-ipv6` feature is off, or this network is v4-only).
+    #[cfg(feature = "proto-ipv6")]
+    let broadcast_addr = if stack.config_v6().is_some() {
+        IpEndpoint::new(
+            IpAddress::Ipv6(Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 0xbeef)),
+            port,
+        )
+    } else {
+        IpEndpoint::new(IpAddress::v4(255, 255, 255, 255), port)
+    };
+    #[cfg(not(feature = "proto-ipv6"))]
+    let broadcast_addr = IpEndpoint::new(IpAddress::v4(255, 255, 255, 255), port);
+
+    // Aggressive power-save delays the radio waking to send each sample
+    // batch, which shows up directly as jitter in the broadcast stream, so
+    // this task requests full-performance mode for as long as it's
+    // continuously streaming (i.e. its whole lifetime, since it never
+    // stops). `/power_mode/<mode>` lets the web server override this back
+    // to power-save if the audio stream isn't actually needed.
+    shared_wifi_core
+        .0
+        .lock()
+        .await
+        .set_power_management(cyw43::PowerManagementMode::Performance)
+        .await;
+
     const NUM_CHANNELS: usize = 1;
     const MAX_UDP_PAYLOAD: usize = 1024;
     const BUFFER_SIZE: usize = 1024;
@@ -250,6 +325,24 @@ async fn main(spawner: Spawner) {
     // Wifi prelude
     info!("Hello World!");
 
+    // With `proto-ipv6` enabled, request dual-stack DHCPv4 + SLAAC so
+    // `udp_stream` can broadcast over IPv6 link-local multicast, which many
+    // networks forward more reliably than a v4 limited broadcast.
+    #[cfg(feature = "proto-ipv6")]
+    let mut embassy_pico_wifi_core = EmbassyPicoWifiCore::connect_to_network_with_config(
+        p.PIN_23,
+        p.PIN_24,
+        p.PIN_25,
+        p.PIN_29,
+        p.PIO0,
+        p.DMA_CH0,
+        spawner,
+        environment_variables,
+        NetworkConfig::DualStackDhcp,
+        cyw43::PowerManagementMode::PowerSave,
+    )
+    .await;
+    #[cfg(not(feature = "proto-ipv6"))]
     let mut embassy_pico_wifi_core = EmbassyPicoWifiCore::connect_to_network(
         p.PIN_23,
         p.PIN_24,
@@ -279,6 +372,15 @@ async fn main(spawner: Spawner) {
         .keep_connection_alive()
     );
 
+    // Take the Bluetooth HCI handle out before the core is moved into the
+    // shared Mutex below, so `ble_task` gets its own owned driver instead of
+    // needing to lock the WiFi core's mutex for the lifetime of the BLE link.
+    #[cfg(feature = "bluetooth")]
+    let bt_driver = embassy_pico_wifi_core
+        .bluetooth
+        .take()
+        .expect("bluetooth already taken");
+
     let shared_wifi_core: SharedEmbassyWifiPicoCore =
         SharedEmbassyWifiPicoCore(make_static!(Mutex::new(embassy_pico_wifi_core)));
     let shared_sensor = SharedSensor(make_static!(Mutex::new(DHT22::new(p.PIN_16, Delay))));
@@ -288,6 +390,14 @@ async fn main(spawner: Spawner) {
         .spawn(wifi_autoheal_task(shared_wifi_core, environment_variables))
         .unwrap();
 
+    #[cfg(feature = "bluetooth")]
+    spawner
+        .spawn(ble_task(
+            bt_driver,
+            BleSharedSensorState(shared_sensor_state.0),
+        ))
+        .unwrap();
+
     spawner
         .spawn(udp_stream(
             app,
@@ -0,0 +1,131 @@
+//! Periodically read a DHT22 sensor and send the data to a Supabase database
+//! using an HTTP POST request, the same as `supabase_ppp.rs`, but over a PPP
+//! link carried on the board's USB CDC-ACM serial port instead of a
+//! UART-connected modem. Only the stack source differs:
+//! `construct_post_request_arguments`/`read_http_response` and the
+//! `HttpClient`/TLS setup are unchanged, so this runs wherever a host is
+//! plugged in over USB but neither WiFi nor a cellular modem is available.
+
+#![no_std]
+#![no_main]
+#![cfg(feature = "ppp")]
+#![feature(type_alias_impl_trait)]
+#![allow(async_fn_in_trait)]
+
+use defmt::{error, info};
+use embassy_dht::dht22::DHT22;
+use embassy_executor::Spawner;
+use embassy_net::dns::DnsSocket;
+use embassy_net::tcp::client::TcpConnection;
+use embassy_net::tcp::client::{TcpClient, TcpClientState};
+use embassy_rp::bind_interrupts;
+use embassy_rp::clocks::RoscRng;
+use embassy_rp::peripherals::USB;
+use embassy_rp::usb::{Driver, InterruptHandler as USBInterruptHandler};
+use embassy_time::{Delay, Duration, Timer};
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use pinot_voir::common::ppp::PicoPppCore;
+use pinot_voir::common::shared_functions::EnvironmentVariables;
+use pinot_voir::common::supabase::{construct_post_request_arguments, read_http_response};
+use pinot_voir::common::wifi::{HttpBuffers, NetworkCore};
+use reqwless::client::{HttpClient, HttpConnection, TlsConfig, TlsVerify};
+use reqwless::request::{Method, RequestBuilder};
+use reqwless::response::Response;
+use static_cell::{StaticCell, make_static};
+
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => USBInterruptHandler<USB>;
+});
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let environment_variables: &'static EnvironmentVariables =
+        make_static!(EnvironmentVariables::new());
+    let p = embassy_rp::init(Default::default());
+    info!("Hello World!");
+
+    static STATE: StaticCell<State> = StaticCell::new();
+    static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONTROL_BUF: StaticCell<[u8; 64]> = StaticCell::new();
+
+    let driver = Driver::new(p.USB, Irqs);
+    let mut usb_builder = embassy_usb::Builder::new(
+        driver,
+        {
+            let mut config = embassy_usb::Config::new(0xc0de, 0xcafe);
+            config.manufacturer = Some("Embassy");
+            config.product = Some("Pico PPP Modem");
+            config.serial_number = Some("12345678");
+            config.max_power = 100;
+            config.max_packet_size_0 = 64;
+            config
+        },
+        CONFIG_DESCRIPTOR.init([0; 256]),
+        BOS_DESCRIPTOR.init([0; 256]),
+        &mut [],
+        CONTROL_BUF.init([0; 64]),
+    );
+
+    let usb_cdc = CdcAcmClass::new(&mut usb_builder, STATE.init(State::new()), 64);
+    let usb = usb_builder.build();
+    spawner.spawn(usb_task(usb)).expect("failed to spawn usb_task");
+
+    let pico_ppp_core = PicoPppCore::new(usb_cdc, spawner).await;
+
+    let mut rng = RoscRng;
+    let seed = rng.next_u64();
+
+    let mut http_buffers = HttpBuffers::new();
+    let tls_config = TlsConfig::new(
+        seed,
+        &mut http_buffers.tls_read_buffer,
+        &mut http_buffers.tls_write_buffer,
+        TlsVerify::None,
+    );
+
+    let client_state: TcpClientState<1, 1024, 1024> = TcpClientState::<1, 1024, 1024>::new();
+    let tcp_client = TcpClient::new(pico_ppp_core.stack(), &client_state);
+    let dns_client = DnsSocket::new(pico_ppp_core.stack());
+    let mut http_client = HttpClient::new_with_tls(&tcp_client, &dns_client, tls_config);
+
+    let mut dht_pin: DHT22<'_, Delay> = DHT22::new(p.PIN_16, Delay);
+    let delay_loop = Duration::from_secs(1800);
+
+    loop {
+        let dht_reading = dht_pin.read().unwrap();
+        let (dht_reading_as_string, headers) =
+            construct_post_request_arguments(dht_reading, &environment_variables)
+                .expect("Failed to read dht reading");
+        let mut request = match http_client
+            .request(Method::POST, environment_variables.supabase_url)
+            .await
+        {
+            Ok(req) => req,
+            Err(e) => {
+                error!("Failed to make HTTP request: {:?}", e);
+                return; // handle the error
+            }
+        }
+        .headers(&headers)
+        .body(dht_reading_as_string.as_bytes());
+        let response: Response<'_, '_, HttpConnection<'_, TcpConnection<'_, 1, 1024, 1024>>> =
+            match request.send(&mut http_buffers.rx_buffer).await {
+                Ok(resp) => resp,
+                Err(_e) => {
+                    error!("Failed to send HTTP request");
+                    return; // handle the error;
+                }
+            };
+
+        read_http_response(response).await;
+        Timer::after(delay_loop).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn usb_task(mut usb: embassy_usb::UsbDevice<'static, Driver<'static, USB>>) -> ! {
+    usb.run().await
+}
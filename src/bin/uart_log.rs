@@ -1,9 +1,10 @@
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 #![feature(type_alias_impl_trait)]
 #![feature(impl_trait_in_assoc_type)]
 
 use bytemuck;
+use core::str::from_utf8;
 use defmt::info;
 use embassy_executor::Spawner;
 use embassy_rp::adc::{Adc, Channel, Config, InterruptHandler as ADCInterruptHandler};
@@ -15,7 +16,7 @@ use embassy_rp::usb::{Driver, InterruptHandler as USBInterruptHandler};
 use embassy_rp::{Peri, bind_interrupts};
 use embassy_time::{Instant, Timer};
 use embassy_usb::UsbDevice;
-use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use embassy_usb::class::cdc_acm::{CdcAcmClass, Receiver, Sender, State};
 use embassy_usb_driver::EndpointError;
 use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
@@ -75,6 +76,11 @@ impl CircularBuffer {
     }
 }
 
+/// RP2040 ADC clock that `AdcConfig::adc_div` divides down to hit the
+/// requested sample rate. Also the ceiling on an achievable `rate_hz`: above
+/// this the divider would truncate to 0 and underflow on `- 1`.
+const ADC_CLOCK_HZ: u32 = 48_000_000;
+
 static AUDIO_BUFFER: Mutex<ThreadModeRawMutex, CircularBuffer> = Mutex::new(CircularBuffer {
     buffer1: [0; 128],
     buffer2: [0; 128],
@@ -82,6 +88,40 @@ static AUDIO_BUFFER: Mutex<ThreadModeRawMutex, CircularBuffer> = Mutex::new(Circ
     read_ready: false,
 });
 
+/// Runtime-tunable acquisition/streaming config, set via the SCPI command
+/// interface on the CDC RX path instead of being baked in at compile time.
+struct AdcConfig {
+    rate_hz: u32,
+    streaming: bool,
+    total_bytes_sent: u32,
+    total_bytes_dropped: u32,
+}
+
+impl AdcConfig {
+    const fn new() -> Self {
+        Self {
+            rate_hz: 8000,
+            streaming: true,
+            total_bytes_sent: 0,
+            total_bytes_dropped: 0,
+        }
+    }
+
+    fn adc_div(&self) -> u16 {
+        (ADC_CLOCK_HZ / self.rate_hz - 1) as u16
+    }
+}
+
+static ADC_CONFIG: Mutex<ThreadModeRawMutex, AdcConfig> = Mutex::new(AdcConfig::new());
+
+/// Whether `rate_hz` is achievable by `AdcConfig::adc_div`'s
+/// `ADC_CLOCK_HZ / rate_hz - 1` divider: anything above `ADC_CLOCK_HZ`
+/// truncates the division to 0 and underflows on the `- 1`, and anything
+/// below 733 Hz gives a divider too large for the `u16` it's cast to.
+fn is_achievable_rate_hz(rate_hz: u32) -> bool {
+    rate_hz > 0 && rate_hz <= ADC_CLOCK_HZ && ADC_CLOCK_HZ / rate_hz - 1 <= u16::MAX as u32
+}
+
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
@@ -111,16 +151,109 @@ async fn main(spawner: Spawner) {
         CONTROL_BUF.init([0; 64]),
     );
 
-    let mut cdc = CdcAcmClass::new(&mut usb_builder, STATE.init(State::new()), 64);
+    let cdc = CdcAcmClass::new(&mut usb_builder, STATE.init(State::new()), 64);
     let usb = usb_builder.build();
+    let (cdc_sender, cdc_receiver) = cdc.split();
+
+    static CDC_SENDER: StaticCell<Mutex<ThreadModeRawMutex, Sender<'static, Driver<'static, USB>>>> =
+        StaticCell::new();
+    let shared_sender = &*CDC_SENDER.init(Mutex::new(cdc_sender));
 
     spawner.spawn(usb_task(usb)).unwrap();
 
     // Spawn ADC task
     spawner.spawn(adc_task(p.ADC, p.DMA_CH0, p.PIN_26)).unwrap();
 
+    // SCPI command interface on the CDC RX path; shares the sender with the
+    // audio transmit loop below so command replies interleave with streaming.
+    spawner
+        .spawn(scpi_task(cdc_receiver, shared_sender))
+        .unwrap();
+
     // Main USB transmission loop
-    usb_transmit_task(cdc).await;
+    usb_transmit_task(shared_sender).await;
+}
+
+/// Dispatches a single newline-terminated SCPI-style command, tolerating the
+/// colon-hierarchy and `?` query suffix conventional to the format.
+async fn dispatch_scpi_command<'d>(
+    line: &str,
+    cdc: &mut Sender<'d, Driver<'d, USB>>,
+) {
+    let line = line.trim();
+    match line {
+        "*IDN?" => {
+            let _ = cdc.write_packet(b"pinot-voir,uart_log,1.0\r\n").await;
+        }
+        "STAR" => {
+            ADC_CONFIG.lock().await.streaming = true;
+            info!("SCPI: streaming started");
+        }
+        "STOP" => {
+            ADC_CONFIG.lock().await.streaming = false;
+            info!("SCPI: streaming stopped");
+        }
+        "MEAS:STAT?" => {
+            let config = ADC_CONFIG.lock().await;
+            let mut reply: heapless::String<64> = heapless::String::new();
+            let _ = core::fmt::write(
+                &mut reply,
+                format_args!(
+                    "{},{}\r\n",
+                    config.total_bytes_sent, config.total_bytes_dropped
+                ),
+            );
+            let _ = cdc.write_packet(reply.as_bytes()).await;
+        }
+        _ => {
+            if let Some(rate) = line.strip_prefix("CONF:RATE ") {
+                match rate.parse::<u32>() {
+                    Ok(rate_hz) if is_achievable_rate_hz(rate_hz) => {
+                        ADC_CONFIG.lock().await.rate_hz = rate_hz;
+                        info!("SCPI: sample rate set to {} Hz", rate_hz);
+                    }
+                    _ => info!("SCPI: invalid CONF:RATE argument"),
+                }
+            } else {
+                info!("SCPI: unrecognized command");
+            }
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn scpi_task(
+    mut cdc_receiver: Receiver<'static, Driver<'static, USB>>,
+    shared_sender: &'static Mutex<ThreadModeRawMutex, Sender<'static, Driver<'static, USB>>>,
+) -> ! {
+    let mut line_buf = [0u8; 64];
+    let mut line_len = 0usize;
+
+    loop {
+        cdc_receiver.wait_connection().await;
+        loop {
+            let mut packet = [0u8; 64];
+            let n = match cdc_receiver.read_packet(&mut packet).await {
+                Ok(n) => n,
+                Err(_) => break,
+            };
+
+            for &byte in &packet[..n] {
+                if byte == b'\n' || byte == b'\r' {
+                    if line_len > 0 {
+                        if let Ok(line) = from_utf8(&line_buf[..line_len]) {
+                            let mut cdc_sender = shared_sender.lock().await;
+                            dispatch_scpi_command(line, &mut cdc_sender).await;
+                        }
+                        line_len = 0;
+                    }
+                } else if line_len < line_buf.len() {
+                    line_buf[line_len] = byte;
+                    line_len += 1;
+                }
+            }
+        }
+    }
 }
 
 #[embassy_executor::task]
@@ -132,17 +265,19 @@ async fn adc_task(
     let mut adc = Adc::new(adc_peripheral, IrqsADC, Config::default());
     let mut p26 = Channel::new_pin(pin, Pull::None);
     let mut dma = dma;
-    const SAMPLE_RATE_HZ: u32 = 8000;
-    const ADC_DIV: u16 = (48_000_000 / SAMPLE_RATE_HZ - 1) as u16;
 
-    info!("ADC task started, sample rate: {} Hz", SAMPLE_RATE_HZ);
+    info!("ADC task started");
 
     loop {
-    let mut guard = AUDIO_BUFFER.lock().await;
-    let buffer = guard.get_write_buffer();
+        // Re-read the divider each block so a `CONF:RATE` command over SCPI
+        // takes effect on the next acquisition without reflashing.
+        let adc_div = ADC_CONFIG.lock().await.adc_div();
+
+        let mut guard = AUDIO_BUFFER.lock().await;
+        let buffer = guard.get_write_buffer();
 
         match adc
-            .read_many(&mut p26, buffer, ADC_DIV, dma.reborrow())
+            .read_many(&mut p26, buffer, adc_div, dma.reborrow())
             .await
         {
             Ok(_) => {
@@ -156,21 +291,28 @@ async fn adc_task(
     }
 }
 
-async fn usb_transmit_task(mut cdc: CdcAcmClass<'static, Driver<'static, USB>>) {
+async fn usb_transmit_task(
+    shared_sender: &'static Mutex<ThreadModeRawMutex, Sender<'static, Driver<'static, USB>>>,
+) {
     let mut stats_timer = Instant::now();
-    let mut total_bytes_sent = 0u32;
-    let mut total_bytes_dropped = 0u32;
 
     loop {
-        cdc.wait_connection().await;
+        shared_sender.lock().await.wait_connection().await;
         info!("USB connected");
 
-        total_bytes_sent = 0;
-        total_bytes_dropped = 0;
+        {
+            let mut config = ADC_CONFIG.lock().await;
+            config.total_bytes_sent = 0;
+            config.total_bytes_dropped = 0;
+        }
         stats_timer = Instant::now();
 
         loop {
-            // Wait for data to be available
+            if !ADC_CONFIG.lock().await.streaming {
+                Timer::after_millis(10).await;
+                continue;
+            }
+
             // Wait for data to be available
             let mut audio_bytes_buf = [0u8; 256]; // Adjust size as needed
             let mut audio_len = 0;
@@ -194,20 +336,23 @@ async fn usb_transmit_task(mut cdc: CdcAcmClass<'static, Driver<'static, USB>>)
             while sent < audio_len {
                 let end = (sent + chunk_size).min(audio_len);
                 let chunk = &audio_bytes_buf[sent..end];
+                let mut cdc = shared_sender.lock().await;
                 match cdc.write_packet(chunk).await {
                     Ok(_) => {
                         sent = end;
-                        total_bytes_sent += chunk.len() as u32;
+                        ADC_CONFIG.lock().await.total_bytes_sent += chunk.len() as u32;
                     }
                     Err(_) => {
                         info!("USB write error");
-                        total_bytes_dropped += (audio_len - sent) as u32;
+                        ADC_CONFIG.lock().await.total_bytes_dropped += (audio_len - sent) as u32;
                         break;
                     }
                 }
             }
 
-            // ...existing code for chunked send loop above now handles all transmission...
+            if stats_timer.elapsed().as_secs() >= 2 {
+                stats_timer = Instant::now();
+            }
         }
     }
 }
@@ -216,3 +361,23 @@ async fn usb_transmit_task(mut cdc: CdcAcmClass<'static, Driver<'static, USB>>)
 async fn usb_task(mut usb: UsbDevice<'static, Driver<'static, USB>>) -> ! {
     usb.run().await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_rates_the_adc_divider_cant_hit() {
+        assert!(!is_achievable_rate_hz(0));
+        assert!(!is_achievable_rate_hz(732));
+        assert!(!is_achievable_rate_hz(ADC_CLOCK_HZ + 1));
+        assert!(!is_achievable_rate_hz(u32::MAX));
+    }
+
+    #[test]
+    fn accepts_rates_within_the_adc_clock_range() {
+        assert!(is_achievable_rate_hz(733));
+        assert!(is_achievable_rate_hz(8000));
+        assert!(is_achievable_rate_hz(ADC_CLOCK_HZ));
+    }
+}
@@ -0,0 +1,72 @@
+//! Turns blocks of centred ADC samples into a loudness figure and writes it
+//! into the sensor/HTTP subsystem's [`SensorState`]. Its only feeder is
+//! `bin/server.rs`'s single-core ADC task; the dual-core pipeline in
+//! `bin/core_audio.rs` does not feed it.
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Receiver;
+use embassy_sync::mutex::Mutex;
+
+use super::sensor_tools::SensorState;
+
+/// Computes the windowed RMS of `samples` (already centred around zero) and
+/// converts it to dBFS, relative to the full-scale amplitude of a 12-bit ADC
+/// reading re-centred to `i16`.
+pub fn rms_dbfs(samples: &[i16]) -> f32 {
+    const FULL_SCALE: f32 = 2048.0;
+
+    if samples.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+
+    let sum_squares: f64 = samples.iter().map(|&s| (s as f64) * (s as f64)).sum();
+    let mean_square = sum_squares / samples.len() as f64;
+    let rms = libm::sqrt(mean_square) as f32;
+
+    20.0 * libm::log10f(rms / FULL_SCALE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silence_is_negative_infinity_dbfs() {
+        assert_eq!(rms_dbfs(&[0; 512]), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn full_scale_square_wave_is_zero_dbfs() {
+        let mut samples = [0i16; 512];
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = if i % 2 == 0 { 2048 } else { -2048 };
+        }
+        assert!((rms_dbfs(&samples)).abs() < 0.01);
+    }
+
+    #[test]
+    fn quieter_signal_is_more_negative() {
+        let loud = [1024i16; 512];
+        let quiet = [256i16; 512];
+        assert!(rms_dbfs(&quiet) < rms_dbfs(&loud));
+    }
+
+    #[test]
+    fn empty_input_is_negative_infinity_dbfs() {
+        assert_eq!(rms_dbfs(&[]), f32::NEG_INFINITY);
+    }
+}
+
+/// Drains centred audio blocks from `receiver`, computes their RMS loudness
+/// in dBFS, and writes it into `sensor_state` alongside the DHT22 readings.
+/// `BLOCK_LEN` is the sample count of each channel item (512 for
+/// `bin/server.rs`'s `LOUDNESS_CHANNEL`).
+pub async fn run_loudness_analysis<const BLOCK_LEN: usize, const CAPACITY: usize>(
+    receiver: Receiver<'static, CriticalSectionRawMutex, [i16; BLOCK_LEN], CAPACITY>,
+    sensor_state: &'static Mutex<CriticalSectionRawMutex, SensorState>,
+) -> ! {
+    loop {
+        let centred_samples = receiver.receive().await;
+        let loudness_dbfs = rms_dbfs(&centred_samples);
+        sensor_state.lock().await.loudness = Some(loudness_dbfs);
+    }
+}
@@ -0,0 +1,267 @@
+//! BLE GATT peripheral exposing the DHT22/ADC `SensorState` over the cyw43
+//! radio's Bluetooth HCI, using the `trouble` host stack. Lets a phone read
+//! sensor data without joining the WiFi network, running concurrently with
+//! the `picoserve` HTTP server.
+//!
+//! Like [`super::wifi::FLASH_NEW_FIRMWARE`], the Bluetooth firmware can
+//! either be baked into the binary or flashed to a fixed flash address
+//! ahead of time; see [`FLASH_NEW_BT_FIRMWARE`].
+
+use bt_hci::controller::ExternalController;
+use cyw43::bluetooth::BtDriver;
+use defmt::info;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Timer};
+use static_cell::StaticCell;
+use trouble_host::prelude::*;
+
+use super::dht22_tools::DHT22;
+use super::sensor_tools::SensorState;
+
+/// Mirrors `wifi::FLASH_NEW_FIRMWARE`: set to `true` to bake the BT firmware
+/// blob into the binary, or `false` to read it back from a fixed flash
+/// address it was pre-flashed to.
+pub const FLASH_NEW_BT_FIRMWARE: bool = false;
+
+const MAX_CONNECTIONS: usize = 1;
+const L2CAP_MTU: usize = 128;
+
+#[gatt_server]
+struct EnvironmentalSensingServer {
+    environmental_sensing: EnvironmentalSensingService,
+    battery: BatteryService,
+}
+
+#[gatt_service(uuid = service::ENVIRONMENTAL_SENSING)]
+struct EnvironmentalSensingService {
+    #[characteristic(uuid = characteristic::TEMPERATURE, read, notify)]
+    temperature: i16,
+    #[characteristic(uuid = characteristic::HUMIDITY, read, notify)]
+    humidity: u16,
+    #[characteristic(uuid = "6c6f7564-6e65-7373-2d62-7269676874-01", read, notify)]
+    sensor_extras: [u8; 8],
+}
+
+/// Battery Service (0x180F) with a single Battery Level characteristic.
+/// The Pico W has no on-board fuel gauge, so this reports a fixed 100% —
+/// present for completeness so GATT clients that expect it don't error out.
+#[gatt_service(uuid = service::BATTERY)]
+struct BatteryService {
+    #[characteristic(uuid = characteristic::BATTERY_LEVEL, read, notify)]
+    level: u8,
+}
+
+#[derive(Clone, Copy)]
+pub struct SharedSensorState(pub &'static Mutex<CriticalSectionRawMutex, SensorState>);
+
+/// Takes the cyw43 Bluetooth HCI driver (its firmware is loaded by
+/// `EmbassyPicoWifiCore::new` when the `bluetooth` feature is enabled),
+/// wraps it in a `bt-hci` controller, and runs the `trouble` GATT server
+/// event loop. While a client is connected it notifies the current
+/// `SensorState` values every 5 seconds; it does not watch `SensorState`
+/// for changes, so unchanged values are re-sent and a change is only sent
+/// on the next 5-second tick.
+#[embassy_executor::task]
+pub async fn ble_task(bt_driver: BtDriver<'static>, shared_sensor_state: SharedSensorState) {
+    let controller: ExternalController<BtDriver<'static>, 8> = ExternalController::new(bt_driver);
+
+    static RESOURCES: StaticCell<HostResources<L2CAP_MTU, MAX_CONNECTIONS, 4>> = StaticCell::new();
+    let resources = RESOURCES.init(HostResources::new());
+
+    let stack = trouble_host::new(controller, resources).set_random_address(Address::random([
+        0x41, 0x42, 0x43, 0x44, 0x45, 0xC0,
+    ]));
+
+    let Host {
+        mut peripheral,
+        mut runner,
+        ..
+    } = stack.build();
+
+    let server = EnvironmentalSensingServer::new_with_config(GapConfig::Peripheral(
+        PeripheralConfig {
+            name: "pinot-voir",
+            appearance: &appearance::sensor::MULTI_SENSOR,
+        },
+    ))
+    .expect("failed to build GATT server");
+
+    let _ = server.battery.level.set(&100);
+
+    info!("BLE: advertising Environmental Sensing Service");
+
+    let advertise_and_notify = async {
+        loop {
+            let advertisement = peripheral
+                .advertise(
+                    &Default::default(),
+                    Advertisement::ConnectableScannableUndirected {
+                        adv_data: &[],
+                        scan_data: &[],
+                    },
+                )
+                .await;
+
+            let connection = match advertisement {
+                Ok(advertiser) => advertiser.accept().await,
+                Err(_) => continue,
+            };
+
+            if let Ok(connection) = connection {
+                // Break back out to re-advertise as soon as a notify fails —
+                // that's the client having disconnected, and this is the
+                // only connection slot (`MAX_CONNECTIONS`), so it's otherwise
+                // stuck unable to serve anyone else ever again.
+                'notify: loop {
+                    let sensor_state = *shared_sensor_state.0.lock().await;
+                    if let Some(temperature) = sensor_state.temperature {
+                        if server
+                            .environmental_sensing
+                            .temperature
+                            .notify(&connection, &((temperature * 100.0) as i16))
+                            .await
+                            .is_err()
+                        {
+                            break 'notify;
+                        }
+                    }
+                    if let Some(humidity) = sensor_state.humidity {
+                        if server
+                            .environmental_sensing
+                            .humidity
+                            .notify(&connection, &((humidity * 100.0) as u16))
+                            .await
+                            .is_err()
+                        {
+                            break 'notify;
+                        }
+                    }
+
+                    let mut extras = [0u8; 8];
+                    extras[0..4]
+                        .copy_from_slice(&sensor_state.loudness.unwrap_or(0.0).to_le_bytes());
+                    extras[4..8]
+                        .copy_from_slice(&sensor_state.brightness.unwrap_or(0.0).to_le_bytes());
+                    if server
+                        .environmental_sensing
+                        .sensor_extras
+                        .notify(&connection, &extras)
+                        .await
+                        .is_err()
+                    {
+                        break 'notify;
+                    }
+
+                    if server.battery.level.notify(&connection, &100).await.is_err() {
+                        break 'notify;
+                    }
+
+                    embassy_time::Timer::after(embassy_time::Duration::from_secs(5)).await;
+                }
+                info!("BLE: client disconnected, re-advertising");
+            }
+        }
+    };
+
+    // The HCI runner has to be polled concurrently with our advertise/notify
+    // loop, or the controller never gets a chance to process incoming
+    // packets or send the ones we queue.
+    embassy_futures::join::join(runner.run(), advertise_and_notify).await;
+}
+
+/// Variant of [`ble_task`] for bins that keep their DHT22 behind a raw
+/// `DHT22` mutex (e.g. `server-supabase`'s `SharedSensor<Delay>`) rather than
+/// a shared [`SensorState`]. Reads the sensor and pushes notifications on the
+/// same 30-minute cadence as the HTTP/Supabase readers, sharing the mutex so
+/// all three consumers see the same underlying DHT22.
+#[embassy_executor::task]
+pub async fn ble_dht22_task(
+    bt_driver: BtDriver<'static>,
+    dht_sensor: &'static Mutex<CriticalSectionRawMutex, DHT22<'static, embassy_time::Delay>>,
+) {
+    const SAMPLING_INTERVAL: Duration = Duration::from_secs(60 * 30);
+
+    let controller: ExternalController<BtDriver<'static>, 8> = ExternalController::new(bt_driver);
+
+    static RESOURCES: StaticCell<HostResources<L2CAP_MTU, MAX_CONNECTIONS, 4>> = StaticCell::new();
+    let resources = RESOURCES.init(HostResources::new());
+
+    let stack = trouble_host::new(controller, resources).set_random_address(Address::random([
+        0x41, 0x42, 0x43, 0x44, 0x45, 0xC1,
+    ]));
+
+    let Host {
+        mut peripheral,
+        mut runner,
+        ..
+    } = stack.build();
+
+    let server = EnvironmentalSensingServer::new_with_config(GapConfig::Peripheral(
+        PeripheralConfig {
+            name: "pinot-voir",
+            appearance: &appearance::sensor::MULTI_SENSOR,
+        },
+    ))
+    .expect("failed to build GATT server");
+
+    let _ = server.battery.level.set(&100);
+
+    info!("BLE: advertising Environmental Sensing Service (DHT22)");
+
+    let advertise_and_notify = async {
+        loop {
+            let advertisement = peripheral
+                .advertise(
+                    &Default::default(),
+                    Advertisement::ConnectableScannableUndirected {
+                        adv_data: &[],
+                        scan_data: &[],
+                    },
+                )
+                .await;
+
+            let connection = match advertisement {
+                Ok(advertiser) => advertiser.accept().await,
+                Err(_) => continue,
+            };
+
+            if let Ok(connection) = connection {
+                // Same reasoning as `ble_task`: re-advertise as soon as a
+                // notify fails instead of spinning on a dead connection.
+                'notify: loop {
+                    if let Ok(reading) = dht_sensor.lock().await.read() {
+                        let temperature = (reading.get_temp() * 100.0) as i16;
+                        let humidity = (reading.get_hum() * 100.0) as u16;
+                        if server
+                            .environmental_sensing
+                            .temperature
+                            .notify(&connection, &temperature)
+                            .await
+                            .is_err()
+                        {
+                            break 'notify;
+                        }
+                        if server
+                            .environmental_sensing
+                            .humidity
+                            .notify(&connection, &humidity)
+                            .await
+                            .is_err()
+                        {
+                            break 'notify;
+                        }
+                        if server.battery.level.notify(&connection, &100).await.is_err() {
+                            break 'notify;
+                        }
+                    }
+
+                    Timer::after(SAMPLING_INTERVAL).await;
+                }
+                info!("BLE: client disconnected, re-advertising");
+            }
+        }
+    };
+
+    embassy_futures::join::join(runner.run(), advertise_and_notify).await;
+}
@@ -0,0 +1,96 @@
+//! Wired Ethernet bring-up (W5500 over SPI) that implements the same
+//! [`super::wifi::NetworkCore`] surface as [`super::wifi::EmbassyPicoWifiCore`],
+//! so the DHT22 POST loop and the MQTT module can run unchanged over either
+//! transport. Only built when the `wiznet` feature is enabled, since it pulls
+//! in `embassy-net-wiznet` and a second SPI peripheral.
+#![cfg(feature = "wiznet")]
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_net::{Config, Stack, StackResources};
+use embassy_net_wiznet::chip::W5500;
+use embassy_net_wiznet::{Device, State as WiznetState};
+use embassy_rp::clocks::RoscRng;
+use embassy_rp::gpio::{Input, Output};
+use embassy_rp::spi::Spi;
+use static_cell::StaticCell;
+
+use super::wifi::{NetworkCore, WEB_TASK_POOL_SIZE};
+
+#[embassy_executor::task]
+async fn ethernet_task(
+    mut runner: embassy_net_wiznet::Runner<
+        'static,
+        W5500,
+        Spi<'static, embassy_rp::peripherals::SPI0, embassy_rp::spi::Async>,
+        Output<'static>,
+        Input<'static>,
+    >,
+) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, Device<'static>>) -> ! {
+    runner.run().await
+}
+
+/// Wired-Ethernet counterpart to `EmbassyPicoWifiCore`: brings a W5500 up
+/// over a spare SPI bus and exposes the same kind of `embassy_net` stack,
+/// so application code (DHT22 POST loop, MQTT publisher) only has to depend
+/// on [`NetworkCore`], not on which radio/PHY is underneath.
+pub struct EmbassyPicoEthernetCore {
+    pub stack: Stack<'static>,
+}
+
+impl NetworkCore for EmbassyPicoEthernetCore {
+    fn stack(&self) -> Stack<'static> {
+        self.stack
+    }
+}
+
+impl EmbassyPicoEthernetCore {
+    /// Brings up the W5500 over `spi` (chip select already wired into the
+    /// `Spi` peripheral) using `int`/`reset` as the interrupt and reset
+    /// lines, then waits for the link to be up.
+    pub async fn new(
+        spi: Spi<'static, embassy_rp::peripherals::SPI0, embassy_rp::spi::Async>,
+        int: Input<'static>,
+        reset: Output<'static>,
+        mac_addr: [u8; 6],
+        spawner: Spawner,
+    ) -> Self {
+        static STATE: StaticCell<WiznetState<8, 8>> = StaticCell::new();
+        let state = STATE.init(WiznetState::new());
+
+        let (device, runner) =
+            embassy_net_wiznet::new(mac_addr, state, spi, int, reset)
+                .await
+                .expect("failed to initialise W5500");
+        spawner
+            .spawn(ethernet_task(runner))
+            .expect("failed to spawn ethernet_task");
+
+        static RESOURCES: StaticCell<StackResources<WEB_TASK_POOL_SIZE>> = StaticCell::new();
+        let mut rng = RoscRng;
+        let seed = rng.next_u64();
+
+        let (stack, runner) = embassy_net::new(
+            device,
+            Config::dhcpv4(Default::default()),
+            RESOURCES.init(StackResources::new()),
+            seed,
+        );
+        spawner
+            .spawn(net_task(runner))
+            .expect("failed to spawn net_task");
+
+        info!("Waiting for Ethernet link...");
+        stack.wait_link_up().await;
+        info!("Waiting for DHCP...");
+        stack.wait_config_up().await;
+        info!("Ethernet stack is up!");
+
+        Self { stack }
+    }
+}
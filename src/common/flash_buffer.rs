@@ -0,0 +1,379 @@
+//! Flash-backed store-and-forward buffer for DHT22 readings that couldn't be
+//! uploaded to Supabase, so a transient WiFi/TLS failure in `read_sensor`
+//! loses nothing instead of dropping the reading (and killing the task).
+//!
+//! Records are fixed-size and written append-only within a 4 KB erase
+//! sector, since NOR flash can only clear bits: a sector is only reusable
+//! after a full erase. `push` writes the next record and advances the tail;
+//! `pop_oldest` tombstones the oldest record and advances the head. When the
+//! tail would overflow the current sector we move to (and erase) the next
+//! one; a sector is only erased right before the tail writes its first
+//! record into it, on every pass around the ring (the first one included).
+
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+/// One erase granule on the RP2040's QSPI flash.
+const SECTOR_SIZE: u32 = 4096;
+/// Fixed record size: a 4-byte status word, an 8-byte timestamp and two
+/// 4-byte floats, padded out to a round number of bytes.
+const RECORD_SIZE: u32 = 32;
+const RECORDS_PER_SECTOR: u32 = SECTOR_SIZE / RECORD_SIZE;
+
+/// Record has never been written (flash after an erase reads as all-`0xFF`).
+const STATUS_EMPTY: u32 = 0xFFFF_FFFF;
+/// Record holds a reading that hasn't been uploaded yet.
+const STATUS_VALID: u32 = 0xA5A5_A5A5;
+/// Record has been uploaded and popped; NOR flash can clear bits in place,
+/// so this is `STATUS_VALID` with every bit cleared.
+const STATUS_POPPED: u32 = 0x0000_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BufferedReading {
+    pub timestamp_secs: u64,
+    pub temperature: f32,
+    pub humidity: f32,
+}
+
+impl BufferedReading {
+    fn to_bytes(self) -> [u8; RECORD_SIZE as usize] {
+        let mut record = [0xFFu8; RECORD_SIZE as usize];
+        record[0..4].copy_from_slice(&STATUS_VALID.to_le_bytes());
+        record[4..12].copy_from_slice(&self.timestamp_secs.to_le_bytes());
+        record[12..16].copy_from_slice(&self.temperature.to_le_bytes());
+        record[16..20].copy_from_slice(&self.humidity.to_le_bytes());
+        record
+    }
+
+    fn from_bytes(record: &[u8]) -> Self {
+        Self {
+            timestamp_secs: u64::from_le_bytes(record[4..12].try_into().unwrap()),
+            temperature: f32::from_le_bytes(record[12..16].try_into().unwrap()),
+            humidity: f32::from_le_bytes(record[16..20].try_into().unwrap()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FlashBufferError {
+    Full,
+    Flash,
+    /// The record at `head` didn't carry [`STATUS_VALID`] (it was
+    /// [`STATUS_EMPTY`], [`STATUS_POPPED`] or an unknown value) — the flash
+    /// contents don't match what the ring buffer's own bookkeeping expects.
+    Corrupt,
+}
+
+/// A circular, flash-backed queue of [`BufferedReading`]s over the region
+/// `[BASE, BASE + LEN)` of `flash`. `LEN` must be a multiple of
+/// [`SECTOR_SIZE`].
+pub struct FlashRingBuffer<F: NorFlash + ReadNorFlash, const BASE: u32, const LEN: u32> {
+    flash: F,
+    /// Offset, relative to `BASE`, of the oldest not-yet-popped record.
+    head: u32,
+    /// Offset, relative to `BASE`, of the next free slot to write into.
+    tail: u32,
+}
+
+impl<F: NorFlash + ReadNorFlash, const BASE: u32, const LEN: u32> FlashRingBuffer<F, BASE, LEN> {
+    /// Reads just the status word of the record at record-index `index`
+    /// (`0..LEN / RECORD_SIZE`), for the recovery scan in [`Self::new`].
+    fn status_at(flash: &mut F, index: u32) -> Result<u32, FlashBufferError> {
+        let mut status_bytes = [0u8; 4];
+        flash
+            .read(BASE + index * RECORD_SIZE, &mut status_bytes)
+            .map_err(|_| FlashBufferError::Flash)?;
+        Ok(u32::from_le_bytes(status_bytes))
+    }
+
+    /// Scans every record in the region to recover `head`/`tail`, so the
+    /// buffer survives a reboot with readings still queued.
+    ///
+    /// `push` never lets the buffer fill completely, so there's always at
+    /// least one [`STATUS_EMPTY`] record, and (since `push` also refuses to
+    /// erase a sector `head` still has unpopped records in) that empty space
+    /// always forms a single contiguous run circularly — VALID/POPPED
+    /// records from `head` forward to `tail`, then EMPTY from `tail` forward
+    /// back around to `head`. Once the ring has wrapped past `LEN` at least
+    /// once, that run no longer starts at offset 0, so "first VALID record
+    /// scanning from offset 0" is not reliably `head`: this walks outward
+    /// from any empty record to find where the empty run starts (`tail`)
+    /// and ends (`head`) instead of assuming a non-wrapped, offset-ordered
+    /// layout.
+    pub fn new(mut flash: F) -> Result<Self, FlashBufferError> {
+        let num_records = LEN / RECORD_SIZE;
+
+        let mut empty_anchor = None;
+        for index in 0..num_records {
+            if Self::status_at(&mut flash, index)? == STATUS_EMPTY {
+                empty_anchor = Some(index);
+                break;
+            }
+        }
+
+        let Some(empty_anchor) = empty_anchor else {
+            // No empty record anywhere: the region was never initialized as
+            // a ring buffer (or is fully corrupted). Treat it as fresh.
+            return Ok(Self {
+                flash,
+                head: 0,
+                tail: 0,
+            });
+        };
+
+        // Walk forward from the anchor to the far end of the empty run:
+        // the first non-empty record found is `head`, the oldest unpopped
+        // record.
+        let mut head_index = empty_anchor;
+        loop {
+            let next = (head_index + 1) % num_records;
+            if next == empty_anchor {
+                // Wrapped all the way around without finding a non-empty
+                // record: every record is empty, i.e. a fresh buffer.
+                return Ok(Self {
+                    flash,
+                    head: 0,
+                    tail: 0,
+                });
+            }
+            if Self::status_at(&mut flash, next)? != STATUS_EMPTY {
+                head_index = next;
+                break;
+            }
+            head_index = next;
+        }
+
+        // Walk backward from the anchor to the near end of the same empty
+        // run: that's `tail`, the next free slot to write into.
+        let mut tail_index = empty_anchor;
+        loop {
+            let prev = (tail_index + num_records - 1) % num_records;
+            if Self::status_at(&mut flash, prev)? != STATUS_EMPTY {
+                break;
+            }
+            tail_index = prev;
+        }
+
+        Ok(Self {
+            flash,
+            head: head_index * RECORD_SIZE,
+            tail: tail_index * RECORD_SIZE,
+        })
+    }
+
+    /// Reads the record at `offset` and checks its status word is
+    /// [`STATUS_VALID`], so callers never decode a reading out of an erased
+    /// (`STATUS_EMPTY`) or already-popped (`STATUS_POPPED`) slot.
+    fn record_at(&mut self, offset: u32, out: &mut [u8; RECORD_SIZE as usize]) -> Result<(), FlashBufferError> {
+        self.flash
+            .read(BASE + offset, out)
+            .map_err(|_| FlashBufferError::Flash)?;
+        let status = u32::from_le_bytes(out[0..4].try_into().unwrap());
+        if status != STATUS_VALID {
+            return Err(FlashBufferError::Corrupt);
+        }
+        Ok(())
+    }
+
+    /// Writes `reading` into the next free slot, erasing the next sector
+    /// first if the tail is about to cross into it.
+    pub fn push(&mut self, reading: BufferedReading) -> Result<(), FlashBufferError> {
+        if self.tail % SECTOR_SIZE == 0 {
+            let sector_start = self.tail;
+            let sector_end = sector_start + SECTOR_SIZE;
+            // `head` still has unpopped records in the sector we're about to
+            // erase: treat that as full rather than erasing them out from
+            // under it. `head == tail` means the buffer is empty even if
+            // head happens to sit at the start of this sector, so that case
+            // is still free to erase.
+            if self.head != self.tail && sector_start <= self.head && self.head < sector_end {
+                return Err(FlashBufferError::Full);
+            }
+            self.flash
+                .erase(BASE + sector_start, BASE + sector_end)
+                .map_err(|_| FlashBufferError::Flash)?;
+        }
+
+        let next_tail = (self.tail + RECORD_SIZE) % LEN;
+        if next_tail == self.head {
+            return Err(FlashBufferError::Full);
+        }
+
+        self.flash
+            .write(BASE + self.tail, &reading.to_bytes())
+            .map_err(|_| FlashBufferError::Flash)?;
+        self.tail = next_tail;
+        Ok(())
+    }
+
+    /// Returns the oldest unpopped reading without consuming it.
+    pub fn peek_oldest(&mut self) -> Result<Option<BufferedReading>, FlashBufferError> {
+        if self.head == self.tail {
+            return Ok(None);
+        }
+        let mut record = [0u8; RECORD_SIZE as usize];
+        self.record_at(self.head, &mut record)?;
+        Ok(Some(BufferedReading::from_bytes(&record)))
+    }
+
+    /// Tombstones the oldest record (clearing its status word in place, a
+    /// valid NOR flash operation since it only clears bits) and advances the
+    /// head to the next record.
+    pub fn pop_oldest(&mut self) -> Result<(), FlashBufferError> {
+        if self.head == self.tail {
+            return Ok(());
+        }
+        self.flash
+            .write(BASE + self.head, &STATUS_POPPED.to_le_bytes())
+            .map_err(|_| FlashBufferError::Flash)?;
+        self.head = (self.head + RECORD_SIZE) % LEN;
+        Ok(())
+    }
+}
+
+const _: () = assert!(SECTOR_SIZE % RECORD_SIZE == 0);
+const _: () = assert!(RECORDS_PER_SECTOR > 0);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_storage::nor_flash::{ErrorType, NorFlashError, NorFlashErrorKind};
+
+    const TEST_LEN: u32 = 2 * SECTOR_SIZE;
+    const RECOVERY_TEST_LEN: u32 = 3 * SECTOR_SIZE;
+
+    #[derive(Debug)]
+    struct MockFlashError;
+
+    impl NorFlashError for MockFlashError {
+        fn kind(&self) -> NorFlashErrorKind {
+            NorFlashErrorKind::Other
+        }
+    }
+
+    /// Plain in-memory stand-in for the RP2040's QSPI flash, just faithful
+    /// enough to exercise `FlashRingBuffer`'s sector bookkeeping. Generic
+    /// over its byte size so tests can pick a region big enough to exercise
+    /// wraparound without paying for it in the common-case tests.
+    #[derive(Clone)]
+    struct MockFlash<const N: usize> {
+        data: [u8; N],
+    }
+
+    impl<const N: usize> MockFlash<N> {
+        fn new() -> Self {
+            Self { data: [0xFFu8; N] }
+        }
+    }
+
+    impl<const N: usize> ErrorType for MockFlash<N> {
+        type Error = MockFlashError;
+    }
+
+    impl<const N: usize> ReadNorFlash for MockFlash<N> {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            N
+        }
+    }
+
+    impl<const N: usize> NorFlash for MockFlash<N> {
+        const WRITE_SIZE: usize = 1;
+        const ERASE_SIZE: usize = SECTOR_SIZE as usize;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            self.data[from as usize..to as usize].fill(0xFF);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            self.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    /// Reproduces the push/pop/push sequence from the chunk2-2 review: fill
+    /// sector 0, pop a single record, fill sector 1 (wrapping the tail back
+    /// to sector 0), then push once more. That last push must be rejected as
+    /// `Full` instead of erasing sector 0 out from under the records `head`
+    /// still hasn't caught up to.
+    #[test]
+    fn push_refuses_to_erase_a_sector_head_still_points_into() {
+        let mut buffer: FlashRingBuffer<MockFlash<{ TEST_LEN as usize }>, 0, TEST_LEN> =
+            FlashRingBuffer::new(MockFlash::new()).unwrap();
+
+        let reading = BufferedReading {
+            timestamp_secs: 0,
+            temperature: 21.0,
+            humidity: 40.0,
+        };
+
+        for _ in 0..RECORDS_PER_SECTOR {
+            buffer.push(reading).unwrap();
+        }
+
+        buffer.pop_oldest().unwrap();
+
+        for _ in 0..RECORDS_PER_SECTOR {
+            buffer.push(reading).unwrap();
+        }
+
+        assert!(matches!(buffer.push(reading), Err(FlashBufferError::Full)));
+
+        // The records head hadn't caught up to must have survived untouched.
+        for _ in 0..(RECORDS_PER_SECTOR - 1) {
+            assert_eq!(buffer.peek_oldest().unwrap(), Some(reading));
+            buffer.pop_oldest().unwrap();
+        }
+    }
+
+    /// Reproduces the wraparound bug from the chunk2-2 re-review: "first
+    /// STATUS_VALID record scanning from offset 0 = head" only holds before
+    /// the ring has ever wrapped. Fill sector 0, fill sector 1, drain sector
+    /// 0 entirely (head jumps to the start of sector 1), fill sector 2
+    /// (tail wraps back to offset 0, erasing sector 0 now that head has
+    /// moved out of it), then push one more record into that freshly-erased
+    /// sector 0. `new()` must recover `head`/`tail` from flash alone and
+    /// land on the same values the live buffer already has, even though the
+    /// lowest-offset `STATUS_VALID` record (freshly written at offset 0) is
+    /// *not* `head` here.
+    #[test]
+    fn new_recovers_head_and_tail_after_wraparound() {
+        type Buffer = FlashRingBuffer<MockFlash<{ RECOVERY_TEST_LEN as usize }>, 0, RECOVERY_TEST_LEN>;
+        let mut buffer: Buffer = FlashRingBuffer::new(MockFlash::new()).unwrap();
+
+        let reading = BufferedReading {
+            timestamp_secs: 0,
+            temperature: 21.0,
+            humidity: 40.0,
+        };
+
+        // Sector 0, then sector 1.
+        for _ in 0..(2 * RECORDS_PER_SECTOR) {
+            buffer.push(reading).unwrap();
+        }
+        // Drain sector 0 completely: head moves to the start of sector 1.
+        for _ in 0..RECORDS_PER_SECTOR {
+            buffer.pop_oldest().unwrap();
+        }
+        // Sector 2, then one more record wrapping the tail back into the
+        // now head-free sector 0 (erasing it).
+        for _ in 0..(RECORDS_PER_SECTOR + 1) {
+            buffer.push(reading).unwrap();
+        }
+
+        assert_eq!(buffer.head, RECORDS_PER_SECTOR * RECORD_SIZE);
+        assert_eq!(buffer.tail, RECORD_SIZE);
+
+        let recovered: Buffer = FlashRingBuffer::new(buffer.flash.clone()).unwrap();
+        assert_eq!(recovered.head, buffer.head);
+        assert_eq!(recovered.tail, buffer.tail);
+    }
+}
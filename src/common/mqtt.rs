@@ -0,0 +1,487 @@
+//! A minimal MQTT 3.1.1 client used to publish sensor data to a broker as an
+//! alternative to the HTTP/Supabase POST path in [`super::supabase`] and
+//! [`super::telemetry`], and to receive `set_led`/`disconnect` commands over
+//! the same link.
+//!
+//! Only the handful of control packets needed to connect, publish, subscribe
+//! and keep the connection alive are implemented; this is not a
+//! general-purpose MQTT stack.
+
+use core::fmt::Write;
+
+use defmt::{error, info};
+use embassy_net::dns::DnsSocket;
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{IpEndpoint, Stack};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+use heapless::String;
+
+use super::dht22_tools::sensor_reading_to_string;
+use super::sensor_tools::SensorState;
+use super::shared_functions::EnvironmentVariables;
+use super::wifi::SharedEmbassyWifiPicoCore;
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const SUBSCRIBE: u8 = 0x82;
+const SUBACK: u8 = 0x90;
+const PINGREQ: u8 = 0xC0;
+const PINGRESP: u8 = 0xD0;
+
+const KEEP_ALIVE_SECS: u16 = 30;
+
+/// How often [`mqtt_telemetry_task`] publishes a `SensorState` snapshot.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Encodes `len` using the MQTT variable-length "remaining length" varint:
+/// 7 data bits per byte, the high bit set while more bytes follow.
+fn encode_remaining_length(mut len: usize, out: &mut heapless::Vec<u8, 4>) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte).expect("remaining length encodes in <=4 bytes");
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+fn push_str(buf: &mut heapless::Vec<u8, 512>, s: &str) -> Result<(), ()> {
+    let len = s.len() as u16;
+    buf.extend_from_slice(&len.to_be_bytes()).map_err(|_| ())?;
+    buf.extend_from_slice(s.as_bytes()).map_err(|_| ())
+}
+
+/// Connects `socket` to `broker_host:broker_port` and performs the MQTT
+/// CONNECT/CONNACK handshake.
+async fn connect(
+    socket: &mut TcpSocket<'_>,
+    stack: Stack<'static>,
+    environment_variables: &EnvironmentVariables,
+    broker_host: &str,
+    broker_port: u16,
+    client_id: &str,
+) -> Result<(), ()> {
+    let dns_client = DnsSocket::new(stack);
+    let addrs = dns_client
+        .query(broker_host, embassy_net::dns::DnsQueryType::A)
+        .await
+        .map_err(|_| ())?;
+    let addr = *addrs.first().ok_or(())?;
+    socket
+        .connect(IpEndpoint::new(addr, broker_port))
+        .await
+        .map_err(|_| ())?;
+
+    let mut variable_header_and_payload: heapless::Vec<u8, 512> = heapless::Vec::new();
+    push_str(&mut variable_header_and_payload, "MQTT")?;
+    let mut connect_flags: u8 = 0x02; // clean session
+    if environment_variables.mqtt_username.is_some() {
+        connect_flags |= 0x80;
+    }
+    if environment_variables.mqtt_password.is_some() {
+        connect_flags |= 0x40;
+    }
+    variable_header_and_payload
+        .extend_from_slice(&[0x04, connect_flags])
+        .map_err(|_| ())?;
+    variable_header_and_payload
+        .extend_from_slice(&KEEP_ALIVE_SECS.to_be_bytes())
+        .map_err(|_| ())?;
+
+    push_str(&mut variable_header_and_payload, client_id)?;
+    if let Some(username) = environment_variables.mqtt_username {
+        push_str(&mut variable_header_and_payload, username)?;
+    }
+    if let Some(password) = environment_variables.mqtt_password {
+        push_str(&mut variable_header_and_payload, password)?;
+    }
+
+    let mut remaining_length: heapless::Vec<u8, 4> = heapless::Vec::new();
+    encode_remaining_length(variable_header_and_payload.len(), &mut remaining_length);
+
+    socket.write(&[CONNECT]).await.map_err(|_| ())?;
+    socket.write(&remaining_length).await.map_err(|_| ())?;
+    socket
+        .write(&variable_header_and_payload)
+        .await
+        .map_err(|_| ())?;
+
+    let mut connack = [0u8; 4];
+    socket.read(&mut connack).await.map_err(|_| ())?;
+    if connack[0] != CONNACK || connack[3] != 0 {
+        error!("MQTT CONNECT rejected: {:?}", connack);
+        return Err(());
+    }
+
+    info!("MQTT connected to {}:{}", broker_host, broker_port);
+    Ok(())
+}
+
+async fn publish(socket: &mut TcpSocket<'_>, topic: &str, payload: &[u8], qos: u8) -> Result<(), ()> {
+    let mut variable_header_and_payload: heapless::Vec<u8, 512> = heapless::Vec::new();
+    push_str(&mut variable_header_and_payload, topic)?;
+    variable_header_and_payload
+        .extend_from_slice(payload)
+        .map_err(|_| ())?;
+
+    let mut remaining_length: heapless::Vec<u8, 4> = heapless::Vec::new();
+    encode_remaining_length(variable_header_and_payload.len(), &mut remaining_length);
+
+    let fixed_header = PUBLISH | (qos << 1);
+    socket.write(&[fixed_header]).await.map_err(|_| ())?;
+    socket.write(&remaining_length).await.map_err(|_| ())?;
+    socket
+        .write(&variable_header_and_payload)
+        .await
+        .map_err(|_| ())
+}
+
+async fn ping(socket: &mut TcpSocket<'_>) -> Result<(), ()> {
+    socket.write(&[PINGREQ, 0x00]).await.map_err(|_| ())
+}
+
+/// Sends a SUBSCRIBE for `topic` at QoS 0 and waits for its SUBACK.
+/// `packet_id` only needs to be unique within the connection, since this
+/// client never has more than one SUBSCRIBE in flight at a time.
+async fn subscribe(socket: &mut TcpSocket<'_>, topic: &str, packet_id: u16) -> Result<(), ()> {
+    let mut variable_header_and_payload: heapless::Vec<u8, 512> = heapless::Vec::new();
+    variable_header_and_payload
+        .extend_from_slice(&packet_id.to_be_bytes())
+        .map_err(|_| ())?;
+    push_str(&mut variable_header_and_payload, topic)?;
+    variable_header_and_payload.push(0x00).map_err(|_| ())?; // requested QoS 0
+
+    let mut remaining_length: heapless::Vec<u8, 4> = heapless::Vec::new();
+    encode_remaining_length(variable_header_and_payload.len(), &mut remaining_length);
+
+    socket.write(&[SUBSCRIBE]).await.map_err(|_| ())?;
+    socket.write(&remaining_length).await.map_err(|_| ())?;
+    socket
+        .write(&variable_header_and_payload)
+        .await
+        .map_err(|_| ())?;
+
+    let mut suback = [0u8; 5];
+    socket.read(&mut suback).await.map_err(|_| ())?;
+    if suback[0] != SUBACK {
+        error!("MQTT SUBSCRIBE rejected for {}: {:?}", topic, suback);
+        return Err(());
+    }
+    Ok(())
+}
+
+/// Publishes a DHT22 reading as a JSON payload to `topic`, connecting and
+/// disconnecting for every call. Suited to the same once-per-reading cadence
+/// as [`super::supabase::construct_post_request_arguments`].
+pub async fn publish_reading(
+    stack: Stack<'static>,
+    environment_variables: &EnvironmentVariables,
+    topic: &str,
+    client_id: &str,
+    dht_reading: embassy_dht::Reading<f32, f32>,
+) -> Result<(), ()> {
+    let broker_host = environment_variables.mqtt_broker_host.ok_or(())?;
+
+    let mut rx_buffer = [0u8; 1024];
+    let mut tx_buffer = [0u8; 1024];
+    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+
+    connect(
+        &mut socket,
+        stack,
+        environment_variables,
+        broker_host,
+        environment_variables.mqtt_broker_port,
+        client_id,
+    )
+    .await?;
+
+    let reading_as_string = sensor_reading_to_string(dht_reading).map_err(|_| ())?;
+    let (humidity, temperature) = (dht_reading.get_hum(), dht_reading.get_temp());
+    let mut json_payload: String<64> = String::new();
+    write!(
+        json_payload,
+        "{{\"temperature\":{temperature},\"humidity\":{humidity}}}"
+    )
+    .map_err(|_| ())?;
+    info!("MQTT publishing: {}", reading_as_string);
+
+    publish(&mut socket, topic, json_payload.as_bytes(), 0).await
+}
+
+/// Keeps a long-lived MQTT connection alive, sending a PINGREQ every half
+/// keep-alive window (`KEEP_ALIVE_SECS / 2`).
+#[embassy_executor::task]
+pub async fn mqtt_keepalive_task(
+    stack: Stack<'static>,
+    environment_variables: &'static EnvironmentVariables,
+) {
+    let Some(broker_host) = environment_variables.mqtt_broker_host else {
+        info!("No MQTT broker configured, keepalive task exiting");
+        return;
+    };
+
+    loop {
+        let mut rx_buffer = [0u8; 1024];
+        let mut tx_buffer = [0u8; 1024];
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+
+        let connected = connect(
+            &mut socket,
+            stack,
+            environment_variables,
+            broker_host,
+            environment_variables.mqtt_broker_port,
+            "pinot-voir-keepalive",
+        )
+        .await;
+
+        match connected {
+            Ok(()) => loop {
+                Timer::after(Duration::from_secs(KEEP_ALIVE_SECS as u64 / 2)).await;
+                if ping(&mut socket).await.is_err() {
+                    error!("MQTT ping failed, reconnecting");
+                    break;
+                }
+            },
+            Err(_) => {
+                error!("MQTT connect failed, retrying in 5s");
+                Timer::after(Duration::from_secs(5)).await;
+            }
+        }
+    }
+}
+
+fn write_opt_f32(out: &mut heapless::String<160>, value: Option<f32>) -> Result<(), core::fmt::Error> {
+    match value {
+        Some(v) => write!(out, "{v}"),
+        None => write!(out, "null"),
+    }
+}
+
+/// Serializes a `SensorState` the same way [`super::telemetry::telemetry_task`]
+/// does for its Supabase POST body, since both end up as the JSON payload of
+/// a single publish.
+fn sensor_state_to_json(sensor_state: &SensorState) -> Result<heapless::String<160>, core::fmt::Error> {
+    let mut json: heapless::String<160> = heapless::String::new();
+    json.push_str("{\"temperature\":")?;
+    write_opt_f32(&mut json, sensor_state.temperature)?;
+    json.push_str(",\"humidity\":")?;
+    write_opt_f32(&mut json, sensor_state.humidity)?;
+    json.push_str(",\"brightness\":")?;
+    write_opt_f32(&mut json, sensor_state.brightness)?;
+    json.push_str(",\"loudness\":")?;
+    write_opt_f32(&mut json, sensor_state.loudness)?;
+    json.push_str("}")?;
+    Ok(json)
+}
+
+/// Decodes an incoming "remaining length" field using the same
+/// continuation-bit varint algorithm [`encode_remaining_length`] uses for
+/// outgoing packets (7 data bits per byte, high bit set while more bytes
+/// follow, up to 4 bytes). A fixed single byte only worked by accident for
+/// payloads under 128 bytes; anything a broker sends with a 2+-byte
+/// remaining-length field would otherwise desync every read after it.
+async fn decode_remaining_length(socket: &mut TcpSocket<'_>) -> Result<usize, ()> {
+    let mut len = 0usize;
+    let mut multiplier = 1usize;
+    for _ in 0..4 {
+        let mut byte = [0u8; 1];
+        socket.read(&mut byte).await.map_err(|_| ())?;
+        len += (byte[0] & 0x7F) as usize * multiplier;
+        if byte[0] & 0x80 == 0 {
+            return Ok(len);
+        }
+        multiplier *= 128;
+    }
+    Err(())
+}
+
+/// Reads and handles one incoming control packet: PINGRESP is dropped
+/// silently, a PUBLISH on `led_topic`/`disconnect_topic` is dispatched into
+/// the same [`super::wifi::EmbassyPicoWifiCore`] methods the HTTP
+/// `/set_led` and `/disconnect` routes call, and anything else is ignored.
+/// Returns `Err(())` on a socket read failure, or on a packet it can't
+/// frame or parse (a remaining length over 4 bytes or over 256, a PUBLISH
+/// too short for its topic, or a non-UTF-8 topic), so the caller knows to
+/// reconnect.
+async fn handle_incoming(
+    socket: &mut TcpSocket<'_>,
+    shared_wifi_core: &SharedEmbassyWifiPicoCore,
+    led_topic: &str,
+    disconnect_topic: &str,
+) -> Result<(), ()> {
+    let mut fixed_header = [0u8; 1];
+    socket.read(&mut fixed_header).await.map_err(|_| ())?;
+
+    let remaining_length = decode_remaining_length(socket).await?;
+    let mut payload = [0u8; 256];
+    let payload = payload.get_mut(..remaining_length).ok_or(())?;
+    socket.read(payload).await.map_err(|_| ())?;
+
+    // Drain the body even for packets we don't act on (e.g. PINGRESP) so the
+    // stream stays framed correctly for the next read; only a PUBLISH's body
+    // is a topic we might dispatch a command from.
+    if fixed_header[0] & 0xF0 != PUBLISH {
+        return Ok(());
+    }
+
+    if payload.len() < 2 {
+        return Err(());
+    }
+    let topic_len = u16::from_be_bytes([payload[0], payload[1]]) as usize;
+    let topic_bytes = payload.get(2..2 + topic_len).ok_or(())?;
+    let topic = core::str::from_utf8(topic_bytes).map_err(|_| ())?;
+
+    if topic == led_topic {
+        let led_is_on = payload.get(2 + topic_len) == Some(&b'1');
+        info!("MQTT command: set_led({})", led_is_on);
+        shared_wifi_core
+            .0
+            .lock()
+            .await
+            .control
+            .gpio_set(0, led_is_on)
+            .await;
+    } else if topic == disconnect_topic {
+        info!("MQTT command: disconnect");
+        shared_wifi_core.0.lock().await.disconnect_from_network().await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Spot-checks `encode_remaining_length` against the worked examples in
+    /// the MQTT 3.1.1 spec (section 2.2.3): one byte up to 127, then the
+    /// continuation bit kicking in at the boundary of each 7-bit group.
+    #[test]
+    fn encode_remaining_length_matches_the_spec_examples() {
+        fn encoded(len: usize) -> heapless::Vec<u8, 4> {
+            let mut out = heapless::Vec::new();
+            encode_remaining_length(len, &mut out);
+            out
+        }
+
+        assert_eq!(encoded(0).as_slice(), &[0x00]);
+        assert_eq!(encoded(127).as_slice(), &[0x7F]);
+        assert_eq!(encoded(128).as_slice(), &[0x80, 0x01]);
+        assert_eq!(encoded(16383).as_slice(), &[0xFF, 0x7F]);
+        assert_eq!(encoded(16384).as_slice(), &[0x80, 0x80, 0x01]);
+        assert_eq!(encoded(2_097_151).as_slice(), &[0xFF, 0xFF, 0x7F]);
+    }
+}
+
+/// Replaces the Supabase HTTP polling loop (see [`super::telemetry::telemetry_task`])
+/// with a single long-lived MQTT connection: subscribes to
+/// `pinot/<client_id>/cmd/set_led` and `pinot/<client_id>/cmd/disconnect` so
+/// those commands can be dispatched the same way the HTTP routes are, then
+/// loops publishing a `SensorState` snapshot to `pinot/<client_id>/sensor`
+/// every [`SAMPLE_INTERVAL`] while servicing the PINGREQ keepalive.
+/// Reconnects from scratch on any socket error.
+#[embassy_executor::task]
+pub async fn mqtt_telemetry_task(
+    shared_wifi_core: SharedEmbassyWifiPicoCore,
+    sensor_state: &'static Mutex<CriticalSectionRawMutex, SensorState>,
+    environment_variables: &'static EnvironmentVariables,
+    client_id: &'static str,
+) {
+    let Some(broker_host) = environment_variables.mqtt_broker_host else {
+        info!("No MQTT broker configured, telemetry task exiting");
+        return;
+    };
+
+    let mut led_topic: String<64> = String::new();
+    let _ = write!(led_topic, "pinot/{client_id}/cmd/set_led");
+    let mut disconnect_topic: String<64> = String::new();
+    let _ = write!(disconnect_topic, "pinot/{client_id}/cmd/disconnect");
+    let mut sensor_topic: String<64> = String::new();
+    let _ = write!(sensor_topic, "pinot/{client_id}/sensor");
+
+    loop {
+        let stack = shared_wifi_core.0.lock().await.stack;
+        let mut rx_buffer = [0u8; 1024];
+        let mut tx_buffer = [0u8; 1024];
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+
+        if connect(
+            &mut socket,
+            stack,
+            environment_variables,
+            broker_host,
+            environment_variables.mqtt_broker_port,
+            client_id,
+        )
+        .await
+        .is_err()
+        {
+            error!("MQTT connect failed, retrying in 5s");
+            Timer::after(Duration::from_secs(5)).await;
+            continue;
+        }
+
+        if subscribe(&mut socket, led_topic.as_str(), 1).await.is_err()
+            || subscribe(&mut socket, disconnect_topic.as_str(), 2)
+                .await
+                .is_err()
+        {
+            error!("MQTT subscribe failed, reconnecting");
+            continue;
+        }
+        info!("MQTT subscribed to {} and {}", led_topic, disconnect_topic);
+
+        let mut next_ping = Instant::now() + Duration::from_secs(KEEP_ALIVE_SECS as u64 / 2);
+        let mut next_publish = Instant::now() + SAMPLE_INTERVAL;
+
+        loop {
+            match embassy_futures::select::select3(
+                Timer::at(next_ping),
+                Timer::at(next_publish),
+                handle_incoming(
+                    &mut socket,
+                    &shared_wifi_core,
+                    led_topic.as_str(),
+                    disconnect_topic.as_str(),
+                ),
+            )
+            .await
+            {
+                embassy_futures::select::Either3::First(()) => {
+                    if ping(&mut socket).await.is_err() {
+                        error!("MQTT ping failed, reconnecting");
+                        break;
+                    }
+                    next_ping = Instant::now() + Duration::from_secs(KEEP_ALIVE_SECS as u64 / 2);
+                }
+                embassy_futures::select::Either3::Second(()) => {
+                    let snapshot = *sensor_state.lock().await;
+                    if let Ok(json) = sensor_state_to_json(&snapshot) {
+                        if publish(&mut socket, sensor_topic.as_str(), json.as_bytes(), 0)
+                            .await
+                            .is_err()
+                        {
+                            error!("MQTT publish failed, reconnecting");
+                            break;
+                        }
+                        info!("MQTT published: {}", json);
+                    }
+                    next_publish = Instant::now() + SAMPLE_INTERVAL;
+                }
+                embassy_futures::select::Either3::Third(Err(())) => {
+                    error!("MQTT connection error, reconnecting");
+                    break;
+                }
+                embassy_futures::select::Either3::Third(Ok(())) => {}
+            }
+        }
+    }
+}
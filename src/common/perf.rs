@@ -0,0 +1,183 @@
+//! Wi-Fi/TCP throughput self-test, mirroring the kind of HIL throughput
+//! harness used to validate cyw43 so antenna placement, power-save modes,
+//! and TLS-vs-plaintext overhead can be regression-tested on real hardware
+//! before deploying the DHT22 sensor loop.
+//!
+//! Disabled unless the `perf_test` feature is enabled, so it never costs
+//! flash/RAM in a normal sensor deployment.
+#![cfg(feature = "perf_test")]
+
+use defmt::info;
+use embassy_net::dns::DnsSocket;
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{IpEndpoint, Stack};
+use embassy_time::{Duration, Instant};
+
+use super::shared_functions::EnvironmentVariables;
+
+const FILLER_BUFFER: [u8; 1024] = [0xAA; 1024];
+
+/// Min/avg/max of the per-second throughput samples plus the total bytes
+/// moved, reported at the end of a benchmark window.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ThroughputStats {
+    pub min_mbit_s: f32,
+    pub avg_mbit_s: f32,
+    pub max_mbit_s: f32,
+    pub total_bytes: u64,
+}
+
+fn mbit_per_sec(bytes: u64, elapsed: Duration) -> f32 {
+    let secs = elapsed.as_millis() as f32 / 1000.0;
+    if secs == 0.0 {
+        return 0.0;
+    }
+    (bytes as f32 * 8.0) / secs / 1_000_000.0
+}
+
+async fn connect_to_perf_server<'a>(
+    stack: Stack<'static>,
+    rx_buffer: &'a mut [u8],
+    tx_buffer: &'a mut [u8],
+    environment_variables: &EnvironmentVariables,
+) -> Result<TcpSocket<'a>, ()> {
+    let host = environment_variables.perf_server_host.ok_or(())?;
+    let dns_client = DnsSocket::new(stack);
+    let addrs = dns_client
+        .query(host, embassy_net::dns::DnsQueryType::A)
+        .await
+        .map_err(|_| ())?;
+    let addr = *addrs.first().ok_or(())?;
+
+    let mut socket = TcpSocket::new(stack, rx_buffer, tx_buffer);
+    socket
+        .connect(IpEndpoint::new(addr, environment_variables.perf_server_port))
+        .await
+        .map_err(|_| ())?;
+    Ok(socket)
+}
+
+/// Writes `FILLER_BUFFER` in a tight loop for `duration`, counting bytes sent
+/// and tracking min/avg/max per-second throughput.
+pub async fn upload_test(socket: &mut TcpSocket<'_>, duration: Duration) -> ThroughputStats {
+    let start = Instant::now();
+    let mut window_start = start;
+    let mut window_bytes: u64 = 0;
+    let mut total_bytes: u64 = 0;
+    let mut min_mbit_s = f32::MAX;
+    let mut max_mbit_s = 0.0f32;
+    let mut second_samples: u32 = 0;
+    let mut sum_mbit_s: f32 = 0.0;
+
+    while start.elapsed() < duration {
+        match socket.write(&FILLER_BUFFER).await {
+            Ok(n) => {
+                window_bytes += n as u64;
+                total_bytes += n as u64;
+            }
+            Err(_) => break,
+        }
+
+        if window_start.elapsed() >= Duration::from_secs(1) {
+            let rate = mbit_per_sec(window_bytes, window_start.elapsed());
+            min_mbit_s = min_mbit_s.min(rate);
+            max_mbit_s = max_mbit_s.max(rate);
+            sum_mbit_s += rate;
+            second_samples += 1;
+            info!("perf upload: {} Mbit/s", rate);
+            window_bytes = 0;
+            window_start = Instant::now();
+        }
+    }
+
+    ThroughputStats {
+        min_mbit_s: if second_samples == 0 { 0.0 } else { min_mbit_s },
+        avg_mbit_s: if second_samples == 0 {
+            0.0
+        } else {
+            sum_mbit_s / second_samples as f32
+        },
+        max_mbit_s,
+        total_bytes,
+    }
+}
+
+/// Reads into a scratch buffer for `duration`, counting bytes received and
+/// tracking min/avg/max per-second throughput.
+pub async fn download_test(socket: &mut TcpSocket<'_>, duration: Duration) -> ThroughputStats {
+    let mut scratch = [0u8; 1024];
+    let start = Instant::now();
+    let mut window_start = start;
+    let mut window_bytes: u64 = 0;
+    let mut total_bytes: u64 = 0;
+    let mut min_mbit_s = f32::MAX;
+    let mut max_mbit_s = 0.0f32;
+    let mut second_samples: u32 = 0;
+    let mut sum_mbit_s: f32 = 0.0;
+
+    while start.elapsed() < duration {
+        match socket.read(&mut scratch).await {
+            Ok(0) => break,
+            Ok(n) => {
+                window_bytes += n as u64;
+                total_bytes += n as u64;
+            }
+            Err(_) => break,
+        }
+
+        if window_start.elapsed() >= Duration::from_secs(1) {
+            let rate = mbit_per_sec(window_bytes, window_start.elapsed());
+            min_mbit_s = min_mbit_s.min(rate);
+            max_mbit_s = max_mbit_s.max(rate);
+            sum_mbit_s += rate;
+            second_samples += 1;
+            info!("perf download: {} Mbit/s", rate);
+            window_bytes = 0;
+            window_start = Instant::now();
+        }
+    }
+
+    ThroughputStats {
+        min_mbit_s: if second_samples == 0 { 0.0 } else { min_mbit_s },
+        avg_mbit_s: if second_samples == 0 {
+            0.0
+        } else {
+            sum_mbit_s / second_samples as f32
+        },
+        max_mbit_s,
+        total_bytes,
+    }
+}
+
+/// Opens one socket to the perf server configured via `EnvironmentVariables`
+/// and runs upload then download tests for `duration_per_direction` each,
+/// reporting Mbit/s totals over `defmt::info!`.
+pub async fn run_throughput_self_test(
+    stack: Stack<'static>,
+    environment_variables: &EnvironmentVariables,
+    duration_per_direction: Duration,
+) {
+    let mut rx_buffer = [0u8; 2048];
+    let mut tx_buffer = [0u8; 2048];
+    let socket = connect_to_perf_server(stack, &mut rx_buffer, &mut tx_buffer, environment_variables).await;
+
+    let mut socket = match socket {
+        Ok(socket) => socket,
+        Err(_) => {
+            info!("perf self-test: failed to connect to perf server");
+            return;
+        }
+    };
+
+    let upload = upload_test(&mut socket, duration_per_direction).await;
+    info!(
+        "perf upload done: min={} avg={} max={} Mbit/s, {} bytes",
+        upload.min_mbit_s, upload.avg_mbit_s, upload.max_mbit_s, upload.total_bytes
+    );
+
+    let download = download_test(&mut socket, duration_per_direction).await;
+    info!(
+        "perf download done: min={} avg={} max={} Mbit/s, {} bytes",
+        download.min_mbit_s, download.avg_mbit_s, download.max_mbit_s, download.total_bytes
+    );
+}
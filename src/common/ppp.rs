@@ -0,0 +1,145 @@
+//! PPP transport over either a UART-connected cellular/serial modem or a
+//! USB CDC ACM link, bringing up the same kind of `embassy_net` stack that
+//! [`super::wifi::EmbassyPicoWifiCore`] exposes so `main` can pick Wi-Fi,
+//! Ethernet, or PPP by feature flag while `construct_post_request_arguments`/
+//! TLS buffer setup stay unchanged.
+#![cfg(feature = "ppp")]
+
+use defmt::info;
+use embassy_executor::Spawner;
+use embassy_net::{Config, Stack, StackResources};
+use embassy_net_ppp::Device;
+use embassy_rp::clocks::RoscRng;
+use embassy_rp::peripherals::{UART0, USB};
+use embassy_rp::uart::{Async, Uart};
+use embassy_rp::usb::Driver;
+use embassy_usb::class::cdc_acm::{CdcAcmClass, Receiver as CdcReceiver, Sender as CdcSender};
+use static_cell::StaticCell;
+
+use super::wifi::{NetworkCore, WEB_TASK_POOL_SIZE};
+
+#[embassy_executor::task]
+async fn ppp_task(mut runner: embassy_net_ppp::Runner<'static>, uart: Uart<'static, UART0, Async>) -> ! {
+    let (mut rx, mut tx) = uart.split();
+    runner
+        .run(&mut rx, &mut tx, embassy_net_ppp::Config::default())
+        .await
+}
+
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, Device<'static>>) -> ! {
+    runner.run().await
+}
+
+/// Cellular/serial-modem counterpart to `EmbassyPicoWifiCore`: brings an
+/// `embassy-net-ppp` link up over a UART and exposes the same kind of
+/// `embassy_net` stack, so the DHT22 POST loop and the MQTT module don't
+/// need to know their stack came from a modem rather than a radio.
+pub struct EmbassyPicoPppCore {
+    pub stack: Stack<'static>,
+}
+
+impl NetworkCore for EmbassyPicoPppCore {
+    fn stack(&self) -> Stack<'static> {
+        self.stack
+    }
+}
+
+impl EmbassyPicoPppCore {
+    /// Runs LCP/IPCP negotiation over `uart` and waits for the resulting
+    /// `embassy_net` stack to come up.
+    pub async fn new(uart: Uart<'static, UART0, Async>, spawner: Spawner) -> Self {
+        static PPP_STATE: StaticCell<embassy_net_ppp::State<4, 4>> = StaticCell::new();
+        let (ppp_device, runner) = embassy_net_ppp::new(PPP_STATE.init(embassy_net_ppp::State::new()));
+
+        spawner
+            .spawn(ppp_task(runner, uart))
+            .expect("failed to spawn ppp_task");
+
+        static RESOURCES: StaticCell<StackResources<WEB_TASK_POOL_SIZE>> = StaticCell::new();
+        let mut rng = RoscRng;
+        let seed = rng.next_u64();
+
+        let (stack, runner) = embassy_net::new(
+            ppp_device,
+            Config::default(),
+            RESOURCES.init(StackResources::new()),
+            seed,
+        );
+        spawner
+            .spawn(net_task(runner))
+            .expect("failed to spawn net_task");
+
+        info!("Waiting for PPP link...");
+        stack.wait_link_up().await;
+        info!("Waiting for IPCP config...");
+        stack.wait_config_up().await;
+        info!("PPP stack is up!");
+
+        Self { stack }
+    }
+}
+
+#[embassy_executor::task]
+async fn usb_ppp_task(
+    mut runner: embassy_net_ppp::Runner<'static>,
+    mut rx: CdcReceiver<'static, Driver<'static, USB>>,
+    mut tx: CdcSender<'static, Driver<'static, USB>>,
+) -> ! {
+    rx.wait_connection().await;
+    runner
+        .run(&mut rx, &mut tx, embassy_net_ppp::Config::default())
+        .await
+}
+
+/// USB-serial counterpart to `EmbassyPicoPppCore`: runs PPP over the same
+/// `CdcAcmClass` the dual-core ADC streamer uses for its audio link, turning
+/// the board's existing USB serial port into a real network transport for
+/// development and headless deployments where no WiFi is available.
+pub struct PicoPppCore {
+    pub stack: Stack<'static>,
+}
+
+impl NetworkCore for PicoPppCore {
+    fn stack(&self) -> Stack<'static> {
+        self.stack
+    }
+}
+
+impl PicoPppCore {
+    /// Splits `usb_cdc` into its reader/writer halves, runs LCP/IPCP
+    /// negotiation over them, and waits for the resulting `embassy_net`
+    /// stack to come up.
+    pub async fn new(usb_cdc: CdcAcmClass<'static, Driver<'static, USB>>, spawner: Spawner) -> Self {
+        let (tx, rx) = usb_cdc.split();
+
+        static PPP_STATE: StaticCell<embassy_net_ppp::State<4, 4>> = StaticCell::new();
+        let (ppp_device, runner) = embassy_net_ppp::new(PPP_STATE.init(embassy_net_ppp::State::new()));
+
+        spawner
+            .spawn(usb_ppp_task(runner, rx, tx))
+            .expect("failed to spawn usb_ppp_task");
+
+        static RESOURCES: StaticCell<StackResources<WEB_TASK_POOL_SIZE>> = StaticCell::new();
+        let mut rng = RoscRng;
+        let seed = rng.next_u64();
+
+        let (stack, runner) = embassy_net::new(
+            ppp_device,
+            Config::default(),
+            RESOURCES.init(StackResources::new()),
+            seed,
+        );
+        spawner
+            .spawn(net_task(runner))
+            .expect("failed to spawn net_task");
+
+        info!("Waiting for USB PPP link...");
+        stack.wait_link_up().await;
+        info!("Waiting for IPCP config...");
+        stack.wait_config_up().await;
+        info!("USB PPP stack is up!");
+
+        Self { stack }
+    }
+}
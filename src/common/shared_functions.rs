@@ -24,6 +24,12 @@ pub struct EnvironmentVariables {
     pub wifi_password: &'static str,
     pub supabase_url: &'static str,
     pub supabase_key: &'static str,
+    pub mqtt_broker_host: Option<&'static str>,
+    pub mqtt_broker_port: u16,
+    pub mqtt_username: Option<&'static str>,
+    pub mqtt_password: Option<&'static str>,
+    pub perf_server_host: Option<&'static str>,
+    pub perf_server_port: u16,
 }
 
 pub fn parse_env_variables() -> EnvironmentVariables {
@@ -32,6 +38,12 @@ pub fn parse_env_variables() -> EnvironmentVariables {
     let mut password: Option<&str> = None;
     let mut supabase_url: Option<&str> = None;
     let mut supabase_key: Option<&str> = None;
+    let mut mqtt_broker_host: Option<&str> = None;
+    let mut mqtt_broker_port: Option<u16> = None;
+    let mut mqtt_username: Option<&str> = None;
+    let mut mqtt_password: Option<&str> = None;
+    let mut perf_server_host: Option<&str> = None;
+    let mut perf_server_port: Option<u16> = None;
 
     for line in env_file.lines() {
         if let Some((key, value)) = line.split_once('=') {
@@ -40,6 +52,12 @@ pub fn parse_env_variables() -> EnvironmentVariables {
                 "PASSWORD" => password = Some(value),
                 "SUPABASE_URL" => supabase_url = Some(value),
                 "SUPABASE_KEY" => supabase_key = Some(value),
+                "MQTT_BROKER_HOST" => mqtt_broker_host = Some(value),
+                "MQTT_BROKER_PORT" => mqtt_broker_port = value.parse().ok(),
+                "MQTT_USERNAME" => mqtt_username = Some(value),
+                "MQTT_PASSWORD" => mqtt_password = Some(value),
+                "PERF_SERVER_HOST" => perf_server_host = Some(value),
+                "PERF_SERVER_PORT" => perf_server_port = value.parse().ok(),
                 _ => {}
             }
         }
@@ -55,6 +73,12 @@ pub fn parse_env_variables() -> EnvironmentVariables {
         wifi_password,
         supabase_url,
         supabase_key,
+        mqtt_broker_host,
+        mqtt_broker_port: mqtt_broker_port.unwrap_or(1883),
+        mqtt_username,
+        mqtt_password,
+        perf_server_host,
+        perf_server_port: perf_server_port.unwrap_or(5201),
     }
 }
 
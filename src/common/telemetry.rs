@@ -0,0 +1,149 @@
+//! End-to-end Supabase telemetry pipeline: periodically snapshots
+//! `SensorState`, POSTs it as JSON, and stays resilient to a flaky link by
+//! buffering unsent readings in a `heapless` ring and replaying them on
+//! reconnect, backing off exponentially on HTTP/TLS errors. Posts with
+//! `supabase_url`/`supabase_key`/`get_api_key_as_bearer_string`.
+
+use core::fmt::Write as _;
+
+use defmt::{error, info};
+use embassy_net::Stack;
+use embassy_net::dns::DnsSocket;
+use embassy_net::tcp::client::{TcpClient, TcpClientState};
+use embassy_rp::clocks::RoscRng;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Timer};
+use heapless::Deque;
+use reqwless::client::{HttpClient, TlsConfig, TlsVerify};
+use reqwless::request::{Method, RequestBuilder};
+
+use super::sensor_tools::SensorState;
+use super::shared_functions::{EnvironmentVariables, get_api_key_as_bearer_string};
+use super::wifi::{HttpBuffers, SharedEmbassyWifiPicoCore, next_wifi_event};
+
+/// How many snapshots to hold while the link is down; once full, the oldest
+/// buffered reading is dropped to make room for the newest.
+const BUFFER_CAPACITY: usize = 8;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+fn write_opt_f32(out: &mut heapless::String<160>, value: Option<f32>) -> core::fmt::Result {
+    match value {
+        Some(v) => write!(out, "{v}"),
+        None => write!(out, "null"),
+    }
+}
+
+fn sensor_state_to_json(sensor_state: &SensorState) -> Result<heapless::String<160>, core::fmt::Error> {
+    let mut json: heapless::String<160> = heapless::String::new();
+    json.push_str("{\"temperature\":")?;
+    write_opt_f32(&mut json, sensor_state.temperature)?;
+    json.push_str(",\"humidity\":")?;
+    write_opt_f32(&mut json, sensor_state.humidity)?;
+    json.push_str(",\"brightness\":")?;
+    write_opt_f32(&mut json, sensor_state.brightness)?;
+    json.push_str(",\"loudness\":")?;
+    write_opt_f32(&mut json, sensor_state.loudness)?;
+    json.push_str("}")?;
+    Ok(json)
+}
+
+/// POSTs a single `SensorState` snapshot to `environment_variables.supabase_url`,
+/// authenticating with the Bearer token built from `supabase_key`.
+async fn post_reading(
+    stack: Stack<'static>,
+    environment_variables: &EnvironmentVariables,
+    sensor_state: &SensorState,
+) -> Result<(), ()> {
+    let body = sensor_state_to_json(sensor_state).map_err(|_| ())?;
+    let bearer = get_api_key_as_bearer_string(environment_variables.supabase_key).map_err(|_| ())?;
+
+    let mut http_buffers = HttpBuffers::new();
+    let mut rng = RoscRng;
+    let seed = rng.next_u64();
+    let client_state: TcpClientState<1, 1024, 1024> = TcpClientState::new();
+    let tcp_client = TcpClient::new(stack, &client_state);
+    let dns_client = DnsSocket::new(stack);
+    let tls_config = TlsConfig::new(
+        seed,
+        &mut http_buffers.tls_read_buffer,
+        &mut http_buffers.tls_write_buffer,
+        TlsVerify::None,
+    );
+    let mut http_client = HttpClient::new_with_tls(&tcp_client, &dns_client, tls_config);
+
+    let headers = [
+        ("Content-Type", "application/json"),
+        ("apikey", environment_variables.supabase_key),
+        ("Authorization", bearer.as_str()),
+    ];
+
+    let mut request = http_client
+        .request(Method::POST, environment_variables.supabase_url)
+        .await
+        .map_err(|e| {
+            error!("Supabase telemetry: failed to open request: {:?}", e);
+        })?
+        .headers(&headers)
+        .body(body.as_bytes());
+
+    request
+        .send(&mut http_buffers.rx_buffer)
+        .await
+        .map_err(|_| error!("Supabase telemetry: POST failed"))?;
+
+    info!("Supabase telemetry: posted {}", body);
+    Ok(())
+}
+
+/// Snapshots `sensor_state` every [`SAMPLE_INTERVAL`], buffering readings
+/// while the link is down and flushing them in order once a
+/// [`super::wifi::WifiEvent::Connected`] event (or the next sample tick)
+/// finds the link back up. Applies exponential backoff between
+/// [`MIN_BACKOFF`] and [`MAX_BACKOFF`] on POST failures.
+#[embassy_executor::task]
+pub async fn telemetry_task(
+    shared_wifi_core: SharedEmbassyWifiPicoCore,
+    sensor_state: &'static Mutex<CriticalSectionRawMutex, SensorState>,
+    environment_variables: &'static EnvironmentVariables,
+) -> ! {
+    let mut buffered: Deque<SensorState, BUFFER_CAPACITY> = Deque::new();
+    let mut backoff = MIN_BACKOFF;
+    let mut subscriber = shared_wifi_core.0.lock().await.subscribe_events();
+
+    loop {
+        let snapshot = *sensor_state.lock().await;
+        if buffered.push_back(snapshot).is_err() {
+            buffered.pop_front();
+            let _ = buffered.push_back(snapshot);
+        }
+
+        let stack = shared_wifi_core.0.lock().await.stack;
+        if stack.is_link_up() {
+            while let Some(reading) = buffered.pop_front() {
+                match post_reading(stack, environment_variables, &reading).await {
+                    Ok(()) => backoff = MIN_BACKOFF,
+                    Err(()) => {
+                        let _ = buffered.push_front(reading);
+                        info!(
+                            "Supabase telemetry: backing off {}s after a failed POST",
+                            backoff.as_secs()
+                        );
+                        Timer::after(backoff).await;
+                        backoff = Duration::from_secs((backoff.as_secs() * 2).min(MAX_BACKOFF.as_secs()));
+                        break;
+                    }
+                }
+            }
+        }
+
+        embassy_futures::select::select(
+            next_wifi_event(&mut subscriber),
+            Timer::after(SAMPLE_INTERVAL),
+        )
+        .await;
+    }
+}
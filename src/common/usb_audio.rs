@@ -0,0 +1,145 @@
+//! USB Audio Class 1.0 (UAC1) streaming interface for the dual-core ADC
+//! example (`bin/uart_log2.rs`), replacing the raw 64-byte CDC-ACM chunks
+//! with a proper isochronous audio endpoint so the Pico enumerates as a
+//! standard microphone/line device (recognized by `arecord`/CoreAudio)
+//! instead of an opaque serial stream.
+//!
+//! Only a single mono, 16-bit, 8 kHz PCM stream is described - enough for
+//! the existing double-buffered `AudioBlock` capture in the ADC example.
+
+use embassy_usb::driver::{Driver, Endpoint, EndpointError, EndpointIn};
+use embassy_usb::Builder;
+
+/// Sample rate baked into the UAC1 format descriptor. At this rate one USB
+/// frame (1 ms) carries 8 samples of 16-bit mono audio, i.e. 16 bytes.
+pub const SAMPLE_RATE_HZ: u32 = 8_000;
+/// Bytes of audio carried per 1 ms USB frame at `SAMPLE_RATE_HZ`, 16-bit mono.
+pub const BYTES_PER_FRAME: usize = (SAMPLE_RATE_HZ as usize / 1000) * 2;
+
+const USB_CLASS_AUDIO: u8 = 0x01;
+const USB_SUBCLASS_AUDIOCONTROL: u8 = 0x01;
+const USB_SUBCLASS_AUDIOSTREAMING: u8 = 0x02;
+
+const AC_HEADER: u8 = 0x01;
+const AC_INPUT_TERMINAL: u8 = 0x02;
+const AC_OUTPUT_TERMINAL: u8 = 0x03;
+const AS_GENERAL: u8 = 0x01;
+const AS_FORMAT_TYPE: u8 = 0x02;
+const FORMAT_TYPE_I: u8 = 0x01;
+const INPUT_TERMINAL_MICROPHONE: u16 = 0x0201;
+const OUTPUT_TERMINAL_USB_STREAMING: u16 = 0x0101;
+
+/// The isochronous IN endpoint the ADC's double-buffer is streamed over.
+/// Wraps the raw `embassy_usb` endpoint so callers don't need to know the
+/// UAC1 descriptor plumbing to send a frame.
+pub struct UsbAudioSender<'d, D: Driver<'d>> {
+    ep_in: D::EndpointIn,
+    _marker: core::marker::PhantomData<&'d ()>,
+}
+
+impl<'d, D: Driver<'d>> UsbAudioSender<'d, D> {
+    /// Sends exactly one USB-frame's worth of PCM bytes (`BYTES_PER_FRAME`).
+    /// Intended to be called once per `wait_connection` service interval,
+    /// mirroring the pacing in `cdc_tx_task`.
+    pub async fn send_frame(&mut self, frame: &[u8; BYTES_PER_FRAME]) -> Result<(), EndpointError> {
+        self.ep_in.write(frame).await
+    }
+
+    pub async fn wait_connection(&mut self) {
+        self.ep_in.wait_enabled().await
+    }
+}
+
+/// Adds the Audio Control + Audio Streaming interfaces (UAC1, type-I PCM,
+/// 1 channel, 16-bit, `SAMPLE_RATE_HZ`) to `builder` and returns a sender
+/// wrapping the allocated isochronous IN endpoint.
+pub fn add_usb_audio_interface<'d, D: Driver<'d>>(builder: &mut Builder<'d, D>) -> UsbAudioSender<'d, D> {
+    let mut function = builder.function(USB_CLASS_AUDIO, USB_SUBCLASS_AUDIOCONTROL, 0x00);
+
+    // Audio Control interface: one input terminal (the ADC "microphone")
+    // feeding one output terminal (USB streaming).
+    let mut ac_interface = function.interface();
+    let ac_interface_number = ac_interface.interface_number();
+    let as_interface_number_placeholder = u8::from(ac_interface_number) + 1;
+    let mut ac_alt = ac_interface.alt_setting(USB_CLASS_AUDIO, USB_SUBCLASS_AUDIOCONTROL, 0x00, None);
+    ac_alt.descriptor(
+        embassy_usb::descriptor::descriptor_type::CS_INTERFACE,
+        &[
+            AC_HEADER,
+            0x00,
+            0x01, // bcdADC 1.00
+            0x09,
+            0x00, // wTotalLength (AC header only; terminals described separately below)
+            0x01,
+            as_interface_number_placeholder,
+        ],
+    );
+    ac_alt.descriptor(
+        embassy_usb::descriptor::descriptor_type::CS_INTERFACE,
+        &[
+            AC_INPUT_TERMINAL,
+            0x01, // bTerminalID
+            (INPUT_TERMINAL_MICROPHONE & 0xff) as u8,
+            (INPUT_TERMINAL_MICROPHONE >> 8) as u8,
+            0x00, // bAssocTerminal
+            0x01, // bNrChannels (mono)
+            0x00,
+            0x00, // wChannelConfig
+            0x00, // iChannelNames
+            0x00, // iTerminal
+        ],
+    );
+    ac_alt.descriptor(
+        embassy_usb::descriptor::descriptor_type::CS_INTERFACE,
+        &[
+            AC_OUTPUT_TERMINAL,
+            0x02, // bTerminalID
+            (OUTPUT_TERMINAL_USB_STREAMING & 0xff) as u8,
+            (OUTPUT_TERMINAL_USB_STREAMING >> 8) as u8,
+            0x00, // bAssocTerminal
+            0x01, // bSourceID (input terminal 1)
+            0x00, // iTerminal
+        ],
+    );
+    drop(ac_alt);
+    drop(function);
+
+    // Audio Streaming interface: carries the isochronous PCM data.
+    let mut function = builder.function(USB_CLASS_AUDIO, USB_SUBCLASS_AUDIOSTREAMING, 0x00);
+    let mut as_interface = function.interface();
+    let mut as_alt_zero_bandwidth = as_interface.alt_setting(USB_CLASS_AUDIO, USB_SUBCLASS_AUDIOSTREAMING, 0x00, None);
+    drop(as_alt_zero_bandwidth);
+
+    let mut as_alt_streaming = as_interface.alt_setting(USB_CLASS_AUDIO, USB_SUBCLASS_AUDIOSTREAMING, 0x00, None);
+    as_alt_streaming.descriptor(
+        embassy_usb::descriptor::descriptor_type::CS_INTERFACE,
+        &[
+            AS_GENERAL,
+            0x02, // bTerminalLink -> output terminal 2
+            0x01, // bDelay
+            0x01,
+            0x00, // wFormatTag = PCM
+        ],
+    );
+    as_alt_streaming.descriptor(
+        embassy_usb::descriptor::descriptor_type::CS_INTERFACE,
+        &[
+            AS_FORMAT_TYPE,
+            FORMAT_TYPE_I,
+            0x01, // bNrChannels (mono)
+            0x02, // bSubframeSize (16-bit)
+            0x10, // bBitResolution
+            0x01, // bSamFreqType: one discrete rate
+            (SAMPLE_RATE_HZ & 0xff) as u8,
+            ((SAMPLE_RATE_HZ >> 8) & 0xff) as u8,
+            ((SAMPLE_RATE_HZ >> 16) & 0xff) as u8,
+        ],
+    );
+
+    let ep_in = as_alt_streaming.endpoint_isochronous_in(BYTES_PER_FRAME as u16, 1);
+
+    UsbAudioSender {
+        ep_in,
+        _marker: core::marker::PhantomData,
+    }
+}
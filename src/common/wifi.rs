@@ -21,6 +21,101 @@ pub const WEB_TASK_POOL_SIZE: usize = 12;
 
 pub const FLASH_NEW_FIRMWARE: bool = false;
 
+/// Common post-link-up behaviour shared by every network transport
+/// (cyw43 WiFi, wired Ethernet, PPP, ...): waiting for the link and DHCP
+/// to come up, and handing out the `embassy_net` stack the DHT22 POST loop
+/// and the MQTT module already consume. Transport-specific bring-up (radio
+/// init, SPI wiring, PPP negotiation) stays in each transport's own
+/// constructor; only the shared waiting/stack-access surface lives here.
+pub trait NetworkCore {
+    /// The `embassy_net` stack for this transport, regardless of link layer.
+    fn stack(&self) -> Stack<'static>;
+
+    /// Waits for the link to come up and DHCP (or static config) to settle.
+    async fn wait_config_up(&self) {
+        self.stack().wait_link_up().await;
+        self.stack().wait_config_up().await;
+    }
+}
+
+impl NetworkCore for EmbassyPicoWifiCore {
+    fn stack(&self) -> Stack<'static> {
+        self.stack
+    }
+
+    #[cfg(feature = "proto-ipv6")]
+    async fn wait_config_up(&self) {
+        self.stack.wait_link_up().await;
+        self.stack.wait_config_up().await;
+        if self.wait_for_ipv6 {
+            self.stack.wait_config_v6_up().await;
+        }
+    }
+}
+
+/// How the WiFi stack should acquire its network configuration. Replaces the
+/// previously hardcoded `Config::dhcpv4(Default::default())` so deployments
+/// without DHCP, or with an IPv6-only segment, can still bring the stack up.
+#[derive(Clone)]
+pub enum NetworkConfig {
+    /// DHCPv4 only; the previous, and still the default, behaviour.
+    DhcpV4,
+    /// A fixed IPv4 address, skipping DHCP entirely.
+    StaticV4 {
+        address: embassy_net::Ipv4Cidr,
+        gateway: Option<embassy_net::Ipv4Address>,
+        dns_servers: heapless::Vec<embassy_net::Ipv4Address, 3>,
+    },
+    /// DHCPv4 alongside SLAAC-configured IPv6, gated behind `proto-ipv6`
+    /// since it requires `embassy-net`'s IPv6 support to be compiled in.
+    #[cfg(feature = "proto-ipv6")]
+    DualStackDhcp,
+}
+
+impl NetworkConfig {
+    fn to_embassy_config(&self) -> Config {
+        match self {
+            NetworkConfig::DhcpV4 => Config::dhcpv4(Default::default()),
+            NetworkConfig::StaticV4 {
+                address,
+                gateway,
+                dns_servers,
+            } => Config::ipv4_static(embassy_net::StaticConfigV4 {
+                address: *address,
+                gateway: *gateway,
+                dns_servers: dns_servers.clone(),
+            }),
+            #[cfg(feature = "proto-ipv6")]
+            NetworkConfig::DualStackDhcp => Config {
+                ipv4: embassy_net::ConfigV4::Dhcp(Default::default()),
+                ipv6: embassy_net::ConfigV6::Dhcp(Default::default()),
+            },
+        }
+    }
+
+    /// Whether `wait_config_up` needs to wait on IPv6 configuration in
+    /// addition to IPv4.
+    #[cfg(feature = "proto-ipv6")]
+    fn waits_for_ipv6(&self) -> bool {
+        matches!(self, NetworkConfig::DualStackDhcp)
+    }
+}
+
+/// Whether the cyw43 radio is joined to an existing network as a client,
+/// broadcasting its own network for provisioning, or (nominally) both.
+/// Mirrors the Sta/Ap/ApSta distinction other embassy WiFi drivers expose,
+/// though the cyw43439 doesn't support true concurrent AP+STA at this
+/// driver layer — [`WifiMode::ApSta`] here only marks the hand-over inside
+/// [`EmbassyPicoWifiCore::reconfigure_as_station`]: the provisioning AP has
+/// been left and the station join is in progress. It never means two
+/// simultaneous radios.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WifiMode {
+    Station,
+    AccessPoint,
+    ApSta,
+}
+
 bind_interrupts!(struct Irqs {
     PIO0_IRQ_0 => InterruptHandler<PIO0>;
 });
@@ -46,6 +141,19 @@ pub struct EmbassyPicoWifiCore {
     pub control: Control<'static>,
     pub tls_config: Option<TlsConfig<'static>>,
     pub stack: Stack<'static>,
+    /// Whether this core currently joined a network as a station or is
+    /// broadcasting its own provisioning access point. See [`WifiMode`].
+    pub mode: WifiMode,
+    /// The cyw43439's Bluetooth HCI transport, initialized alongside WiFi
+    /// when the `bluetooth` feature is enabled so `common::ble` can drive it.
+    /// `Option` so a bin can `.take()` it out to hand to a BLE task without
+    /// having to move the whole (`Mutex`-wrapped) core.
+    #[cfg(feature = "bluetooth")]
+    pub bluetooth: Option<cyw43::bluetooth::BtDriver<'static>>,
+    /// Set when `network_config` is [`NetworkConfig::DualStackDhcp`], so
+    /// `wait_config_up` knows to also wait on SLAAC-configured IPv6.
+    #[cfg(feature = "proto-ipv6")]
+    wait_for_ipv6: bool,
 }
 
 impl EmbassyPicoWifiCore {
@@ -57,6 +165,8 @@ impl EmbassyPicoWifiCore {
         pio_0: Peri<'static, PIO0>,
         dma_ch0: Peri<'static, DMA_CH0>,
         spawner: Spawner,
+        network_config: NetworkConfig,
+        power_mode: cyw43::PowerManagementMode,
     ) -> Self {
         let fw: &[u8];
         let clm: &[u8];
@@ -78,7 +188,7 @@ impl EmbassyPicoWifiCore {
 
         let pwr = Output::new(pin_23, Level::Low);
         let cs = Output::new(pin_25, Level::High);
-        let config = Config::dhcpv4(Default::default());
+        let config = network_config.to_embassy_config();
         let mut pio = Pio::new(pio_0, Irqs);
         let spi = PioSpi::new(
             &mut pio.common,
@@ -92,15 +202,25 @@ impl EmbassyPicoWifiCore {
         );
         static STATE: StaticCell<cyw43::State> = StaticCell::new();
         let state = STATE.init(cyw43::State::new());
+
+        #[cfg(feature = "bluetooth")]
+        let (net_device, bt_device, mut control, runner) = {
+            let btfw = if crate::common::ble::FLASH_NEW_BT_FIRMWARE {
+                include_bytes!("../../cyw43-firmware/43439A0_btfw.bin").as_slice()
+            } else {
+                unsafe { core::slice::from_raw_parts(0x10141000 as *const u8, 6164) }
+            };
+            cyw43::new_with_bluetooth(state, pwr, spi, fw, btfw).await
+        };
+        #[cfg(not(feature = "bluetooth"))]
         let (net_device, mut control, runner) = cyw43::new(state, pwr, spi, fw).await;
+
         spawner
             .spawn(wifi_task(runner))
             .expect("failed to spawn wifi_task");
 
         control.init(clm).await;
-        control
-            .set_power_management(cyw43::PowerManagementMode::PowerSave)
-            .await;
+        control.set_power_management(power_mode).await;
 
         static RESOURCES: StaticCell<StackResources<WEB_TASK_POOL_SIZE>> = StaticCell::new();
         let mut rng = RoscRng;
@@ -121,9 +241,17 @@ impl EmbassyPicoWifiCore {
             control,
             tls_config: None,
             stack,
+            mode: WifiMode::Station,
+            #[cfg(feature = "bluetooth")]
+            bluetooth: Some(bt_device),
+            #[cfg(feature = "proto-ipv6")]
+            wait_for_ipv6: network_config.waits_for_ipv6(),
         }
     }
 
+    /// Joins the network using [`NetworkConfig::DhcpV4`]; see
+    /// [`Self::connect_to_network_with_config`] to use a static address or
+    /// dual-stack IPv6 instead.
     pub async fn connect_to_network(
         pin_23: Peri<'static, PIN_23>,
         pin_24: Peri<'static, PIN_24>,
@@ -134,8 +262,52 @@ impl EmbassyPicoWifiCore {
         spawner: Spawner,
         environment_variables: &EnvironmentVariables,
     ) -> Self {
-        let mut embassy_pico_wifi_core =
-            EmbassyPicoWifiCore::new(pin_23, pin_24, pin_25, pin_29, pio0, dma_ch0, spawner).await;
+        Self::connect_to_network_with_config(
+            pin_23,
+            pin_24,
+            pin_25,
+            pin_29,
+            pio0,
+            dma_ch0,
+            spawner,
+            environment_variables,
+            NetworkConfig::DhcpV4,
+            cyw43::PowerManagementMode::PowerSave,
+        )
+        .await
+    }
+
+    /// Like [`Self::connect_to_network`], but lets the caller pick the
+    /// network config and the radio's initial [`cyw43::PowerManagementMode`]
+    /// (see [`Self::set_power_management`] to change it again at runtime —
+    /// e.g. the UDP audio task requesting `Performance` for the duration it
+    /// streams, since aggressive power-save adds latency to both the web
+    /// server and the audio broadcast).
+    #[allow(clippy::too_many_arguments)]
+    pub async fn connect_to_network_with_config(
+        pin_23: Peri<'static, PIN_23>,
+        pin_24: Peri<'static, PIN_24>,
+        pin_25: Peri<'static, PIN_25>,
+        pin_29: Peri<'static, PIN_29>,
+        pio0: Peri<'static, PIO0>,
+        dma_ch0: Peri<'static, DMA_CH0>,
+        spawner: Spawner,
+        environment_variables: &EnvironmentVariables,
+        network_config: NetworkConfig,
+        power_mode: cyw43::PowerManagementMode,
+    ) -> Self {
+        let mut embassy_pico_wifi_core = EmbassyPicoWifiCore::new(
+            pin_23,
+            pin_24,
+            pin_25,
+            pin_29,
+            pio0,
+            dma_ch0,
+            spawner,
+            network_config,
+            power_mode,
+        )
+        .await;
 
         let successful_join = embassy_pico_wifi_core
             .join_wpa2_network(
@@ -157,6 +329,93 @@ impl EmbassyPicoWifiCore {
         embassy_pico_wifi_core
     }
 
+    /// Default address the provisioning access point answers on, the same
+    /// way most consumer routers' own setup pages do.
+    const AP_ADDRESS: embassy_net::Ipv4Address = embassy_net::Ipv4Address::new(192, 168, 4, 1);
+
+    /// Brings the cyw43 radio up as its own access point instead of joining
+    /// an existing network, so a freshly-flashed board with no stored
+    /// credentials can be configured over its own network rather than
+    /// needing a recompile. Serves a static `192.168.4.1/24` address; there's
+    /// no DHCP server here, so a client needs either a static address in
+    /// that subnet or to be told the board's IP directly.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn start_ap(
+        pin_23: Peri<'static, PIN_23>,
+        pin_24: Peri<'static, PIN_24>,
+        pin_25: Peri<'static, PIN_25>,
+        pin_29: Peri<'static, PIN_29>,
+        pio0: Peri<'static, PIO0>,
+        dma_ch0: Peri<'static, DMA_CH0>,
+        spawner: Spawner,
+        ssid: &str,
+        passphrase: Option<&str>,
+    ) -> Self {
+        let ap_config = NetworkConfig::StaticV4 {
+            address: embassy_net::Ipv4Cidr::new(Self::AP_ADDRESS, 24),
+            gateway: None,
+            dns_servers: heapless::Vec::new(),
+        };
+
+        let mut embassy_pico_wifi_core = EmbassyPicoWifiCore::new(
+            pin_23,
+            pin_24,
+            pin_25,
+            pin_29,
+            pio0,
+            dma_ch0,
+            spawner,
+            ap_config,
+            // The provisioning AP is short-lived and all it does is serve
+            // HTTP, so there's no idle stretch worth power-saving through.
+            cyw43::PowerManagementMode::Performance,
+        )
+        .await;
+
+        match passphrase {
+            Some(passphrase) => {
+                embassy_pico_wifi_core
+                    .control
+                    .start_ap_wpa2(ssid, passphrase, 6)
+                    .await;
+            }
+            None => {
+                embassy_pico_wifi_core.control.start_ap_open(ssid, 6).await;
+            }
+        }
+        embassy_pico_wifi_core.mode = WifiMode::AccessPoint;
+        info!("Access point '{}' started on {}", ssid, Self::AP_ADDRESS);
+
+        embassy_pico_wifi_core.wait_config_up().await;
+        embassy_pico_wifi_core
+    }
+
+    /// Tears down the provisioning access point and rejoins the configured
+    /// network as a station, switching the stack back to DHCP in the
+    /// process. Called once `/provision` has received real network
+    /// credentials.
+    pub async fn reconfigure_as_station(
+        &mut self,
+        wifi_ssid: &str,
+        wifi_password: &str,
+    ) -> Result<(), cyw43::ControlError> {
+        info!("Leaving access point mode");
+        self.control.leave().await;
+        self.stack
+            .set_config_v4(NetworkConfig::DhcpV4.to_embassy_config().ipv4);
+        self.mode = WifiMode::ApSta;
+        let result = self.join_wpa2_network(wifi_ssid, wifi_password).await;
+        self.mode = WifiMode::Station;
+        result
+    }
+
+    /// Makes a single join attempt. Callers that want retry-with-backoff
+    /// across repeated failures (e.g. [`wifi_autoheal_task`]) should loop on
+    /// this themselves, re-acquiring the core's mutex for each attempt,
+    /// rather than this method looping internally while holding the lock
+    /// the whole time — a real outage can take minutes to clear, and this is
+    /// the one lock every other consumer (`/set_led`, `/disconnect`,
+    /// `/power_mode`, `mqtt_telemetry_task`) also needs.
     async fn join_wpa2_network(
         &mut self,
         wifi_ssid: &str,
@@ -164,18 +423,11 @@ impl EmbassyPicoWifiCore {
     ) -> Result<(), cyw43::ControlError> {
         info!("Joining network: {}", wifi_ssid);
         info!("Using password: {}", wifi_password);
-        while let Err(err) = self
-            .control
+        self.control
             .join(wifi_ssid, JoinOptions::new(wifi_password.as_bytes()))
-            .await
-        {
-            info!("join failed with status={}", err.status);
-        }
-        info!("waiting for link...");
-        self.stack.wait_link_up().await;
-
-        info!("waiting for DHCP...");
-        self.stack.wait_config_up().await;
+            .await?;
+        info!("waiting for link and network config...");
+        self.wait_config_up().await;
 
         info!("Stack is up!");
         Ok(())
@@ -186,6 +438,60 @@ impl EmbassyPicoWifiCore {
         self.control.leave().await;
         info!("Left network.");
     }
+
+    /// Switches the cyw43 radio's power-management mode at runtime, e.g. to
+    /// drop into `Performance` while the UDP audio task is actively
+    /// streaming and back to `PowerSave` once it's done. Aggressive
+    /// power-save modes delay the radio waking to service incoming frames,
+    /// which shows up as added latency on both the HTTP server and the audio
+    /// broadcast, so callers should only stay in `Performance` for as long
+    /// as that latency actually matters.
+    pub async fn set_power_management(&mut self, mode: cyw43::PowerManagementMode) {
+        info!("Setting WiFi power management mode");
+        self.control.set_power_management(mode).await;
+    }
+
+    /// Subscribes to the cyw43 firmware's connectivity event queue so
+    /// callers can `.await` the next join/disassociation transition instead
+    /// of polling `stack.is_link_up()` on a timer.
+    pub fn subscribe_events(&mut self) -> cyw43::EventSubscriber<'static> {
+        self.control.events().subscribe()
+    }
+}
+
+/// A simplified view of the cyw43 firmware's raw connectivity events,
+/// surfaced so application code can react to a join/disconnect without
+/// depending on the underlying `cyw43::Event` wire representation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WifiEvent {
+    Connected,
+    Disconnected,
+}
+
+/// Maps a raw cyw43 event to a [`WifiEvent`], if it's one we react to.
+fn classify_event(event: &cyw43::Event) -> Option<WifiEvent> {
+    match event.status {
+        cyw43::EventStatus::Success if event.event_type == cyw43::EventType::Join => {
+            Some(WifiEvent::Connected)
+        }
+        _ if event.event_type == cyw43::EventType::Disassoc
+            || event.event_type == cyw43::EventType::Deauth =>
+        {
+            Some(WifiEvent::Disconnected)
+        }
+        _ => None,
+    }
+}
+
+/// Waits for the next [`WifiEvent`] on `subscriber`, discarding any raw
+/// firmware events we don't care about in between.
+pub async fn next_wifi_event(subscriber: &mut cyw43::EventSubscriber<'static>) -> WifiEvent {
+    loop {
+        let event = subscriber.next_message().await;
+        if let Some(wifi_event) = classify_event(&event) {
+            return wifi_event;
+        }
+    }
 }
 
 pub struct HttpBuffers {
@@ -210,24 +516,72 @@ impl HttpBuffers {
     }
 }
 
+/// Reacts to cyw43 connectivity events as they arrive instead of polling
+/// `stack.is_link_up()` on a timer: a disconnect/deauth event fires the
+/// rejoin logic immediately, and a watchdog timer remains as a safety net
+/// in case an event is missed. This avoids holding the core mutex across a
+/// whole idle poll cycle, reducing contention with the HTTP server.
+///
+/// Backs off between repeated join failures instead of hammering the radio
+/// with back-to-back attempts while the AP is still unreachable, but only
+/// holds the core mutex for each individual
+/// [`EmbassyPicoWifiCore::join_wpa2_network`] attempt, releasing it during
+/// the backoff sleep in between — a real outage can take minutes to clear,
+/// and that lock is shared with `/set_led`, `/disconnect`, `/power_mode`
+/// and `mqtt_telemetry_task`.
 #[embassy_executor::task]
 pub async fn wifi_autoheal_task(
     shared_wifi_core: SharedEmbassyWifiPicoCore,
     env: &'static EnvironmentVariables,
 ) {
-    const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+    const WATCHDOG_INTERVAL: Duration = Duration::from_secs(30);
+    const MIN_REJOIN_BACKOFF: Duration = Duration::from_secs(1);
+    const MAX_REJOIN_BACKOFF: Duration = Duration::from_secs(30);
+
+    let mut subscriber = shared_wifi_core.0.lock().await.subscribe_events();
+
     loop {
-        let mut wifi_core = shared_wifi_core.0.lock().await;
-        if !wifi_core.stack.is_link_up() {
+        let link_down = match embassy_futures::select::select(
+            next_wifi_event(&mut subscriber),
+            Timer::after(WATCHDOG_INTERVAL),
+        )
+        .await
+        {
+            embassy_futures::select::Either::First(WifiEvent::Disconnected) => true,
+            embassy_futures::select::Either::First(WifiEvent::Connected) => false,
+            embassy_futures::select::Either::Second(()) => {
+                let wifi_core = shared_wifi_core.0.lock().await;
+                !wifi_core.stack.is_link_up()
+            }
+        };
+
+        if link_down {
             info!("WiFi link down, attempting reconnection...");
-            match wifi_core
-                .join_wpa2_network(env.wifi_ssid, env.wifi_password)
-                .await
-            {
-                Ok(_) => info!("Rejoined WiFi."),
-                Err(e) => info!("WiFi rejoin failed: status={}", e.status),
+            let mut backoff = MIN_REJOIN_BACKOFF;
+            loop {
+                let mut wifi_core = shared_wifi_core.0.lock().await;
+                match wifi_core
+                    .join_wpa2_network(env.wifi_ssid, env.wifi_password)
+                    .await
+                {
+                    Ok(_) => {
+                        info!("Rejoined WiFi.");
+                        blink_n_times(&mut wifi_core.control, 1).await;
+                        break;
+                    }
+                    Err(e) => {
+                        drop(wifi_core);
+                        info!(
+                            "WiFi rejoin failed with status={}, retrying in {}s",
+                            e.status,
+                            backoff.as_secs()
+                        );
+                        Timer::after(backoff).await;
+                        backoff =
+                            Duration::from_secs((backoff.as_secs() * 2).min(MAX_REJOIN_BACKOFF.as_secs()));
+                    }
+                }
             }
         }
-        Timer::after(RECONNECT_DELAY).await;
     }
 }